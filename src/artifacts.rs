@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The --keep-artifacts directory: the capture, Tesseract's input and output,
+/// and the translation requests of one run, for debugging bad results.
+pub struct Artifacts {
+    dir: PathBuf,
+    prefix: String,
+}
+
+impl Artifacts {
+    pub fn create(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            prefix: String::new(),
+        })
+    }
+
+    /// The same directory, with file names prefixed for region `n` of --multi.
+    pub fn for_region(&self, n: usize) -> Self {
+        Self {
+            dir: self.dir.clone(),
+            prefix: format!("region-{n}-"),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}{name}", self.prefix))
+    }
+
+    /// Writes `name`; failures are reported without stopping the run.
+    pub fn save(&self, name: &str, contents: impl AsRef<[u8]>) {
+        let path = self.path(name);
+        if let Err(e) = fs::write(&path, contents) {
+            eprintln!("(Warning) could not write {}: {e}", path.display());
+        }
+    }
+
+    /// Moves or copies `from` in as `name`.
+    pub fn keep(&self, name: &str, from: &Path, keep_original: bool) {
+        let path = self.path(name);
+        let result = match keep_original {
+            true => fs::copy(from, &path).map(drop),
+            false => fs::rename(from, &path),
+        };
+        if let Err(e) = result {
+            eprintln!("(Warning) could not keep {}: {e}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_files_are_prefixed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let artifacts = Artifacts::create(&tmp.path().join("run")).unwrap();
+        artifacts.save("ocr.txt", "hello");
+        artifacts.for_region(2).save("ocr.txt", "world");
+
+        let dir = tmp.path().join("run");
+        assert_eq!(fs::read_to_string(dir.join("ocr.txt")).unwrap(), "hello");
+        assert_eq!(
+            fs::read_to_string(dir.join("region-2-ocr.txt")).unwrap(),
+            "world"
+        );
+
+        let src = tmp.path().join("capture.png");
+        fs::write(&src, "png").unwrap();
+        artifacts.keep("capture.png", &src, true);
+        assert!(src.exists() && dir.join("capture.png").exists());
+    }
+}
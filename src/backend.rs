@@ -0,0 +1,833 @@
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+use crate::config::DeeplEndpoint;
+use crate::glossary::{Glossary, check_glossary_pair, find_glossary};
+use crate::http::{RetryPolicy, is_provider_failure, tag_timeout};
+use crate::llm::{LlmConfig, LlmEngine};
+use crate::local::{
+    CommandBackendConfig, CommandEngine, LibreTranslate, LibreTranslateConfig, Unavailable,
+};
+use crate::protect::{Masked, Protection};
+use crate::translate::{
+    DeeplApi, DeeplLanguage, DeeplOptions, Translation, translate_deepl, translate_deepl_many,
+    validate_deepl_options,
+};
+
+/// Name of the DeepL account configured through --deepl-api-key/$DEEPL_API_KEY/config.
+pub const DEFAULT_BACKEND: &str = "deepl";
+
+/// `[backends.NAME]` in config.toml.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackendConfig {
+    Deepl(DeeplBackendConfig),
+    Command(CommandBackendConfig),
+    Libretranslate(LibreTranslateConfig),
+    Openai(LlmConfig),
+}
+
+impl BackendConfig {
+    /// Whether using this backend leaves the machine (refused by --offline).
+    pub fn needs_network(&self) -> bool {
+        match self {
+            BackendConfig::Deepl(_) => true,
+            BackendConfig::Command(_) => false,
+            BackendConfig::Libretranslate(c) => !c.is_local(),
+            BackendConfig::Openai(c) => !c.is_local(),
+        }
+    }
+
+    /// The backend's own API key, if it has one.
+    pub fn api_key(&self) -> Option<&str> {
+        match self {
+            BackendConfig::Deepl(c) => c.api_key.as_deref(),
+            BackendConfig::Command(_) => None,
+            BackendConfig::Libretranslate(c) => c.api_key.as_deref(),
+            BackendConfig::Openai(c) => c.api_key.as_deref(),
+        }
+    }
+}
+
+/// Whether the backend called `name` needs the network; the default DeepL
+/// account and unknown names do.
+pub fn needs_network(name: &str, backends: &BTreeMap<String, BackendConfig>) -> bool {
+    backends.get(name).is_none_or(BackendConfig::needs_network)
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DeeplBackendConfig {
+    /// Defaults to the main DeepL key.
+    pub api_key: Option<String>,
+    /// "free", "pro" or a URL; defaults to the key's own endpoint.
+    pub endpoint: Option<String>,
+    /// DeepL glossary name or ID on this account.
+    pub glossary: Option<String>,
+    #[serde(flatten)]
+    pub options: DeeplOptions,
+}
+
+/// Which way a request goes. Back-translations skip the DeepL options and
+/// glossary, which were set up for the forward direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Back,
+}
+
+/// A translation service ready to be called.
+pub enum Backend {
+    Deepl {
+        api: DeeplApi,
+        options: DeeplOptions,
+        glossary: Option<Glossary>,
+        /// Target languages, fetched once for strict formality checks.
+        languages: OnceCell<Vec<DeeplLanguage>>,
+    },
+    Command(CommandEngine),
+    Libretranslate(LibreTranslate),
+    Llm(LlmEngine),
+}
+
+impl Backend {
+    pub fn deepl(api: DeeplApi, options: DeeplOptions) -> Self {
+        Backend::Deepl {
+            api,
+            options,
+            glossary: None,
+            languages: OnceCell::new(),
+        }
+    }
+
+    /// `ocr_confidence` (0–100) is passed on to backends that can use it.
+    pub async fn from_config(
+        cfg: &BackendConfig,
+        client: &reqwest::Client,
+        retry: &RetryPolicy,
+        default_key: Option<&str>,
+        ocr_confidence: Option<f32>,
+    ) -> Result<Self> {
+        match cfg {
+            BackendConfig::Deepl(c) => {
+                let api_key = match (c.api_key.as_deref(), default_key) {
+                    (Some(k), _) | (None, Some(k)) => k.to_string(),
+                    (None, None) => bail!("No DeepL API key for this backend"),
+                };
+                let endpoint = match c.endpoint.as_deref() {
+                    Some(e) => DeeplEndpoint::parse(e)?,
+                    None => DeeplEndpoint::for_key(&api_key),
+                };
+                let api = DeeplApi {
+                    client: client.clone(),
+                    retry: retry.clone(),
+                    base_url: endpoint.url().to_string(),
+                    api_key,
+                };
+                let mut backend = Backend::deepl(api, c.options.clone());
+                if let Some(name) = &c.glossary {
+                    backend.use_glossary(name).await?;
+                }
+                Ok(backend)
+            }
+            BackendConfig::Command(c) => Ok(Backend::Command(CommandEngine::from_config(c).await?)),
+            BackendConfig::Libretranslate(c) => Ok(Backend::Libretranslate(
+                LibreTranslate::connect(c, client, retry).await?,
+            )),
+            BackendConfig::Openai(c) => Ok(Backend::Llm(LlmEngine::new(
+                c,
+                client,
+                retry,
+                ocr_confidence,
+            )?)),
+        }
+    }
+
+    /// Looks up a DeepL glossary by name or ID on this backend's account.
+    pub async fn use_glossary(&mut self, name: &str) -> Result<()> {
+        match self {
+            Backend::Deepl { api, glossary, .. } => {
+                *glossary = Some(find_glossary(api, name).await?);
+            }
+            _ => bail!("Glossaries are only supported by DeepL backends"),
+        }
+        Ok(())
+    }
+
+    pub fn glossary(&self) -> Option<&Glossary> {
+        match self {
+            Backend::Deepl { glossary, .. } => glossary.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// `on_token` receives partial output from backends that stream; the
+    /// others ignore it and return the whole text at once.
+    pub async fn translate(
+        &self,
+        text: &str,
+        src: &str,
+        tgt: &str,
+        direction: Direction,
+        on_token: Option<&dyn Fn(&str)>,
+    ) -> Result<Translation> {
+        match self {
+            Backend::Deepl {
+                api,
+                options,
+                glossary,
+                languages,
+            } => {
+                let options = match direction {
+                    Direction::Forward => request_options(options, glossary.as_ref(), src, tgt),
+                    Direction::Back => DeeplOptions::default(),
+                };
+                validate_deepl_options(api, languages, tgt, &options).await?;
+                let (text, detected_source) =
+                    translate_deepl(api, text, tgt, Some(src), &options).await?;
+                Ok(Translation {
+                    target: tgt.to_string(),
+                    text,
+                    detected_source,
+                    provider: None,
+                    back: None,
+                })
+            }
+            Backend::Command(engine) => Ok(plain_translation(
+                tgt,
+                engine.translate(text, src, tgt).await?,
+            )),
+            Backend::Libretranslate(engine) => Ok(plain_translation(
+                tgt,
+                engine.translate(text, src, tgt).await?,
+            )),
+            Backend::Llm(engine) => Ok(plain_translation(
+                tgt,
+                engine.translate(text, src, tgt, on_token).await?,
+            )),
+        }
+    }
+
+    /// Translates `texts` into `tgt`, in one request for DeepL and one call
+    /// per text elsewhere.
+    pub async fn translate_many(
+        &self,
+        texts: &[&str],
+        src: &str,
+        tgt: &str,
+    ) -> Result<Vec<Translation>> {
+        match self {
+            Backend::Deepl {
+                api,
+                options,
+                glossary,
+                languages,
+            } => {
+                let options = request_options(options, glossary.as_ref(), src, tgt);
+                validate_deepl_options(api, languages, tgt, &options).await?;
+                let results = translate_deepl_many(api, texts, tgt, Some(src), &options).await?;
+                Ok(results
+                    .into_iter()
+                    .map(|(text, detected_source)| Translation {
+                        target: tgt.to_string(),
+                        text,
+                        detected_source,
+                        provider: None,
+                        back: None,
+                    })
+                    .collect())
+            }
+            _ => {
+                let mut out = Vec::with_capacity(texts.len());
+                for text in texts {
+                    out.push(
+                        self.translate(text, src, tgt, Direction::Forward, None)
+                            .await?,
+                    );
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Language pairs already warned about, so watch and batch runs say it once.
+static GLOSSARY_SKIPPED: Mutex<BTreeSet<(String, String, String)>> = Mutex::new(BTreeSet::new());
+
+/// A glossary only applies to targets matching its language pair; the
+/// others are translated without it, with a warning.
+fn request_options(
+    options: &DeeplOptions,
+    glossary: Option<&Glossary>,
+    src: &str,
+    tgt: &str,
+) -> DeeplOptions {
+    let mut options = options.clone();
+    options.glossary_id = match glossary {
+        Some(g) if check_glossary_pair(g, src, tgt).is_ok() => Some(g.glossary_id.clone()),
+        Some(g) => {
+            let key = (g.glossary_id.clone(), src.to_string(), tgt.to_string());
+            if GLOSSARY_SKIPPED.lock().expect("warning lock").insert(key) {
+                eprintln!(
+                    "(Warning) glossary {} doesn't cover {src}→{tgt}; translating without it",
+                    g.name
+                );
+            }
+            None
+        }
+        None => None,
+    };
+    options
+}
+
+/// A translation from a backend that reports nothing but the text.
+fn plain_translation(tgt: &str, text: String) -> Translation {
+    Translation {
+        target: tgt.to_string(),
+        text,
+        detected_source: None,
+        provider: None,
+        back: None,
+    }
+}
+
+/// Translates into one target, masking protected tokens around the call.
+pub async fn translate_target(
+    backend: &Backend,
+    text: &str,
+    src: &str,
+    tgt: &str,
+    protect: Option<&Protection>,
+    direction: Direction,
+    on_token: Option<&dyn Fn(&str)>,
+) -> Result<Translation> {
+    let protector = match protect {
+        Some(p) => p.for_pair(src, tgt)?,
+        None => None,
+    };
+    let masked = protector.as_ref().map(|p| p.mask(text));
+    let request_text = masked.as_ref().map_or(text, |m| m.text.as_str());
+
+    let mut translation = backend
+        .translate(request_text, src, tgt, direction, on_token)
+        .await
+        .map_err(tag_timeout)?;
+    if let Some(m) = &masked {
+        unmask(m, &mut translation);
+    }
+    Ok(translation)
+}
+
+/// `translate_target` for several texts at once.
+pub async fn translate_target_many(
+    backend: &Backend,
+    texts: &[&str],
+    src: &str,
+    tgt: &str,
+    protect: Option<&Protection>,
+) -> Result<Vec<Translation>> {
+    let protector = match protect {
+        Some(p) => p.for_pair(src, tgt)?,
+        None => None,
+    };
+    let masked: Option<Vec<Masked>> = protector
+        .as_ref()
+        .map(|p| texts.iter().map(|t| p.mask(t)).collect());
+    let request_texts: Vec<&str> = match &masked {
+        Some(m) => m.iter().map(|m| m.text.as_str()).collect(),
+        None => texts.to_vec(),
+    };
+
+    let mut translations = backend
+        .translate_many(&request_texts, src, tgt)
+        .await
+        .map_err(tag_timeout)?;
+    for (translation, m) in translations.iter_mut().zip(masked.iter().flatten()) {
+        unmask(m, translation);
+    }
+    Ok(translations)
+}
+
+fn unmask(masked: &Masked, translation: &mut Translation) {
+    let (restored, missing) = masked.restore(&translation.text);
+    if !missing.is_empty() {
+        eprintln!(
+            "(Warning) the translator dropped {} protected token(s) for {}.",
+            missing.len(),
+            translation.target
+        );
+    }
+    translation.text = restored;
+}
+
+/// Backends tried in order: quota, auth, rate-limit, server and network
+/// errors fall through to the next one.
+pub struct Chain {
+    backends: Vec<(String, Backend)>,
+    /// Every attempt, when recording for --keep-artifacts.
+    log: Option<Mutex<Vec<serde_json::Value>>>,
+}
+
+impl Chain {
+    /// Keeps the backends that could be set up; fails only if none could.
+    pub fn new(candidates: Vec<(String, Result<Backend>)>) -> Result<Self> {
+        let total = candidates.len();
+        let mut backends = Vec::new();
+        let mut last_err = None;
+        for (name, backend) in candidates {
+            match backend {
+                Ok(b) => backends.push((name, b)),
+                Err(e) if total > 1 => {
+                    eprintln!("(Fallback) skipping {name}: {e:#}");
+                    last_err = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        match last_err {
+            Some(e) if backends.is_empty() => Err(e.context("No usable translation backend")),
+            _ => Ok(Chain {
+                backends,
+                log: None,
+            }),
+        }
+    }
+
+    /// Keeps each request and its response (or error) for `take_log`.
+    pub fn recording(mut self) -> Self {
+        self.log = Some(Mutex::new(Vec::new()));
+        self
+    }
+
+    /// The attempts recorded so far, oldest first.
+    pub fn take_log(&self) -> Vec<serde_json::Value> {
+        self.log
+            .as_ref()
+            .map(|log| std::mem::take(&mut *log.lock().expect("log lock")))
+            .unwrap_or_default()
+    }
+
+    fn record(&self, name: &str, text: &str, src: &str, tgt: &str, result: &Result<Translation>) {
+        let Some(log) = &self.log else {
+            return;
+        };
+        let mut entry = json!({
+            "backend": name,
+            "request": { "source": src, "target": tgt, "text": text },
+        });
+        match result {
+            Ok(t) => entry["response"] = json!(t),
+            Err(e) => entry["error"] = json!(format!("{e:#}")),
+        }
+        log.lock().expect("log lock").push(entry);
+    }
+
+    /// Whether the output should say which provider answered.
+    fn reports_provider(&self) -> bool {
+        self.backends.len() > 1
+    }
+
+    /// Translates `text` back into the source language, without the forward
+    /// direction's DeepL options.
+    pub async fn translate_back(
+        &self,
+        text: &str,
+        src: &str,
+        tgt: &str,
+        protect: Option<&Protection>,
+    ) -> Result<Translation> {
+        self.translate_in(text, src, tgt, protect, Direction::Back, None)
+            .await
+    }
+
+    /// Translates `text`, passing partial output to `on_token` as it arrives.
+    pub async fn translate_streaming(
+        &self,
+        text: &str,
+        src: &str,
+        tgt: &str,
+        protect: Option<&Protection>,
+        on_token: Option<&dyn Fn(&str)>,
+    ) -> Result<Translation> {
+        self.translate_in(text, src, tgt, protect, Direction::Forward, on_token)
+            .await
+    }
+
+    async fn translate_in(
+        &self,
+        text: &str,
+        src: &str,
+        tgt: &str,
+        protect: Option<&Protection>,
+        direction: Direction,
+        on_token: Option<&dyn Fn(&str)>,
+    ) -> Result<Translation> {
+        let (name, mut t) = self
+            .first_success(tgt, async |name, backend| {
+                let result =
+                    translate_target(backend, text, src, tgt, protect, direction, on_token).await;
+                self.record(name, text, src, tgt, &result);
+                result
+            })
+            .await?;
+        if self.reports_provider() {
+            t.provider = Some(name.to_string());
+        }
+        Ok(t)
+    }
+
+    /// Translates several texts into `tgt`, batched where the backend allows.
+    pub async fn translate_many(
+        &self,
+        texts: &[&str],
+        src: &str,
+        tgt: &str,
+        protect: Option<&Protection>,
+    ) -> Result<Vec<Translation>> {
+        let (name, mut translations) = self
+            .first_success(tgt, async |_, backend| {
+                translate_target_many(backend, texts, src, tgt, protect).await
+            })
+            .await?;
+        if self.reports_provider() {
+            for t in &mut translations {
+                t.provider = Some(name.to_string());
+            }
+        }
+        Ok(translations)
+    }
+
+    /// Runs `attempt` on each backend in turn until one succeeds; returns
+    /// the backend's name with the result.
+    async fn first_success<T>(
+        &self,
+        tgt: &str,
+        attempt: impl AsyncFn(&str, &Backend) -> Result<T>,
+    ) -> Result<(&str, T)> {
+        let mut iter = self.backends.iter().peekable();
+        while let Some((name, backend)) = iter.next() {
+            match attempt(name, backend).await {
+                Ok(t) => return Ok((name, t)),
+                Err(e) if is_provider_failure(&e) || e.is::<Unavailable>() => match iter.peek() {
+                    Some((next, _)) => {
+                        eprintln!("(Fallback) {name} failed for {tgt}: {e:#}; trying {next}");
+                    }
+                    None => return Err(e.context(format!("All backends failed for {tgt}"))),
+                },
+                Err(e) => return Err(e.context(format!("{name} failed for {tgt}"))),
+            }
+        }
+        bail!("No translation backend configured")
+    }
+}
+
+/// One backend's attempt at one target in compare mode.
+#[derive(Debug)]
+pub struct Comparison {
+    pub backend: String,
+    pub target: String,
+    pub latency: Duration,
+    pub result: Result<Translation, String>,
+}
+
+/// Runs `text` through every backend for every target concurrently. Failures
+/// (including backends that could not be set up) are kept per entry.
+pub async fn compare(
+    backends: &[(String, Result<Backend>)],
+    text: &str,
+    src: &str,
+    targets: &[String],
+    protect: Option<&Protection>,
+) -> Vec<Comparison> {
+    let jobs = targets.iter().flat_map(|tgt| {
+        backends.iter().map(move |(name, backend)| async move {
+            let start = Instant::now();
+            let result = match backend {
+                Ok(b) => translate_target(b, text, src, tgt, protect, Direction::Forward, None)
+                    .await
+                    .map_err(|e| format!("{e:#}")),
+                Err(e) => Err(format!("{e:#}")),
+            };
+            Comparison {
+                backend: name.clone(),
+                target: tgt.clone(),
+                latency: start.elapsed(),
+                result,
+            }
+        })
+    });
+    futures::future::join_all(jobs).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translate::Formality;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    fn deepl_at(server: &MockServer, key: &str) -> Backend {
+        let api = DeeplApi {
+            client: reqwest::Client::new(),
+            retry: RetryPolicy {
+                max_retries: 0,
+                ..RetryPolicy::default()
+            },
+            api_key: key.into(),
+            base_url: server.base_url(),
+        };
+        Backend::deepl(api, DeeplOptions::default())
+    }
+
+    #[tokio::test]
+    async fn chain_falls_through_on_quota_and_reports_provider() {
+        let server = MockServer::start();
+        let spent = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .header("Authorization", "DeepL-Auth-Key spent");
+            then.status(456);
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .header("Authorization", "DeepL-Auth-Key backup");
+            then.status(200)
+                .json_body(json!({"translations": [{"text": "Hallo"}]}));
+        });
+
+        let chain = Chain::new(vec![
+            ("main".into(), Ok(deepl_at(&server, "spent"))),
+            ("broken".into(), Err(anyhow::anyhow!("no key"))),
+            ("backup".into(), Ok(deepl_at(&server, "backup"))),
+        ])
+        .unwrap()
+        .recording();
+        let t = chain
+            .translate_streaming("Hello", "EN", "DE", None, None)
+            .await
+            .unwrap();
+        assert_eq!(t.text, "Hallo");
+        assert_eq!(t.provider.as_deref(), Some("backup"));
+        spent.assert_hits(1);
+
+        let log = chain.take_log();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0]["backend"], "main");
+        assert!(log[0]["error"].as_str().unwrap().contains("456"));
+        assert_eq!(log[1]["request"]["text"], "Hello");
+        assert_eq!(log[1]["response"]["text"], "Hallo");
+        assert!(chain.take_log().is_empty());
+    }
+
+    #[tokio::test]
+    async fn chain_batches_texts_and_falls_through() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .header("Authorization", "DeepL-Auth-Key spent");
+            then.status(456);
+        });
+        let backup = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .header("Authorization", "DeepL-Auth-Key backup")
+                .json_body_partial(r#"{"text":["Hello","Bye"]}"#);
+            then.status(200)
+                .json_body(json!({"translations": [{"text": "Hallo"}, {"text": "Tschüss"}]}));
+        });
+
+        let chain = Chain::new(vec![
+            ("main".into(), Ok(deepl_at(&server, "spent"))),
+            ("backup".into(), Ok(deepl_at(&server, "backup"))),
+        ])
+        .unwrap();
+        let out = chain
+            .translate_many(&["Hello", "Bye"], "EN", "DE", None)
+            .await
+            .unwrap();
+        let texts: Vec<_> = out.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, ["Hallo", "Tschüss"]);
+        assert!(out.iter().all(|t| t.provider.as_deref() == Some("backup")));
+        backup.assert_hits(1);
+    }
+
+    #[tokio::test]
+    async fn strict_formality_fetches_languages_once() {
+        let server = MockServer::start();
+        let languages = server.mock(|when, then| {
+            when.method(GET).path("/v2/languages");
+            then.status(200)
+                .json_body(json!([{"language": "DE", "supports_formality": true}]));
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/v2/translate");
+            then.status(200)
+                .json_body(json!({"translations": [{"text": "Hallo"}]}));
+        });
+
+        let Backend::Deepl { api, .. } = deepl_at(&server, "k") else {
+            unreachable!()
+        };
+        let options = DeeplOptions {
+            formality: Some(Formality::More),
+            ..Default::default()
+        };
+        let backend = Backend::deepl(api, options);
+        for text in ["Hello", "Hi"] {
+            backend
+                .translate(text, "EN", "DE", Direction::Forward, None)
+                .await
+                .unwrap();
+        }
+        backend.translate_many(&["Hey"], "EN", "DE").await.unwrap();
+        languages.assert_hits(1);
+    }
+
+    #[tokio::test]
+    async fn chain_stops_on_request_errors() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .header("Authorization", "DeepL-Auth-Key main");
+            then.status(400)
+                .body("Value for 'target_lang' not supported.");
+        });
+        let backup = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .header("Authorization", "DeepL-Auth-Key backup");
+            then.status(200)
+                .json_body(json!({"translations": [{"text": "Hallo"}]}));
+        });
+
+        let chain = Chain::new(vec![
+            ("main".into(), Ok(deepl_at(&server, "main"))),
+            ("backup".into(), Ok(deepl_at(&server, "backup"))),
+        ])
+        .unwrap();
+        assert!(
+            chain
+                .translate_streaming("Hello", "EN", "DE", None, None)
+                .await
+                .is_err()
+        );
+        backup.assert_hits(0);
+    }
+
+    #[test]
+    fn glossary_applies_only_to_its_pair() {
+        let g = Glossary {
+            glossary_id: "g1".into(),
+            name: "ui".into(),
+            source_lang: "en".into(),
+            target_lang: "de".into(),
+            entry_count: 1,
+            ready: true,
+        };
+        let options = DeeplOptions::default();
+        let de = request_options(&options, Some(&g), "EN", "DE");
+        assert_eq!(de.glossary_id.as_deref(), Some("g1"));
+        assert!(
+            request_options(&options, Some(&g), "EN", "FR")
+                .glossary_id
+                .is_none()
+        );
+        assert!(GLOSSARY_SKIPPED.lock().unwrap().contains(&(
+            "g1".to_string(),
+            "EN".to_string(),
+            "FR".to_string()
+        )));
+    }
+
+    #[test]
+    fn backend_config_is_tagged_by_type() {
+        let cfg: BackendConfig = toml::from_str(
+            r#"
+type = "deepl"
+api_key = "k:fx"
+endpoint = "free"
+formality = "less"
+"#,
+        )
+        .unwrap();
+        let BackendConfig::Deepl(c) = cfg else {
+            panic!("expected a DeepL backend");
+        };
+        assert_eq!(c.api_key.as_deref(), Some("k:fx"));
+        assert_eq!(c.endpoint.as_deref(), Some("free"));
+        assert!(c.options.formality.is_some());
+    }
+
+    #[test]
+    fn only_local_backends_work_offline() {
+        let backends: BTreeMap<String, BackendConfig> = toml::from_str(
+            r#"
+[argos]
+type = "command"
+command = ["argos-translate", "--from", "{source}", "--to", "{target}"]
+
+[libre-local]
+type = "libretranslate"
+url = "http://127.0.0.1:5000"
+
+[libre-remote]
+type = "libretranslate"
+url = "https://translate.example.com"
+
+[llama]
+type = "openai"
+url = "http://localhost:8080/v1"
+model = "qwen2.5-7b-instruct"
+
+[gpt]
+type = "openai"
+model = "gpt-4o-mini"
+"#,
+        )
+        .unwrap();
+        assert!(!needs_network("argos", &backends));
+        assert!(!needs_network("libre-local", &backends));
+        assert!(needs_network("libre-remote", &backends));
+        assert!(!needs_network("llama", &backends));
+        assert!(needs_network("gpt", &backends));
+        assert!(needs_network(DEFAULT_BACKEND, &backends));
+    }
+
+    #[tokio::test]
+    async fn compare_keeps_going_when_one_backend_fails() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .header("Authorization", "DeepL-Auth-Key good");
+            then.status(200)
+                .json_body(json!({"translations": [{"text": "Hallo"}]}));
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .header("Authorization", "DeepL-Auth-Key spent");
+            then.status(456);
+        });
+
+        let backends = vec![
+            ("good".to_string(), Ok(deepl_at(&server, "good"))),
+            ("spent".to_string(), Ok(deepl_at(&server, "spent"))),
+            ("broken".to_string(), Err(anyhow::anyhow!("no key"))),
+        ];
+        let results = compare(&backends, "Hello", "EN", &["DE".to_string()], None).await;
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].result.as_ref().unwrap().text, "Hallo");
+        assert!(results[1].result.as_ref().unwrap_err().contains("quota"));
+        assert_eq!(results[2].result.as_ref().unwrap_err(), "no key");
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::backend::Chain;
+use crate::protect::Protection;
+use crate::translate::{Translation, deepl_source_for_target, deepl_target};
+
+/// The translation translated back into the source language.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BackTranslation {
+    pub text: String,
+    /// 0.0 (nothing in common) to 1.0 (identical after normalisation).
+    pub similarity: f64,
+    pub below_threshold: bool,
+}
+
+/// Translates `translation` back into `src` with the same backends and scores
+/// it against the OCR text. The forward direction's DeepL options (formality,
+/// context, glossary) are left out. Target-only codes (EN-GB, PT-BR, …) become the
+/// matching source code first.
+pub async fn back_translate(
+    chain: &Chain,
+    ocr_text: &str,
+    src: &str,
+    translation: &Translation,
+    threshold: f64,
+    protect: Option<&Protection>,
+) -> Result<BackTranslation> {
+    let back_src = deepl_source_for_target(&translation.target)?;
+    let back_tgt = deepl_target(src)?;
+    let back = chain
+        .translate_back(&translation.text, &back_src, &back_tgt, protect)
+        .await?;
+    let similarity = similarity(ocr_text, &back.text);
+    Ok(BackTranslation {
+        text: back.text,
+        similarity,
+        below_threshold: similarity < threshold,
+    })
+}
+
+/// Normalised Levenshtein similarity over lower-cased letters and digits, so
+/// punctuation and spacing differences don't count.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    strsim::normalized_levenshtein(&normalize(a), &normalize(b))
+}
+
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+    use crate::http::RetryPolicy;
+    use crate::translate::{DeeplApi, DeeplOptions, Formality};
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn back_translation_drops_forward_options() {
+        let server = MockServer::start();
+        let languages = server.mock(|when, then| {
+            when.method(GET).path("/v2/languages");
+            then.status(200).json_body(json!([
+                {"language": "DE", "supports_formality": true},
+                {"language": "EN-GB", "supports_formality": false},
+                {"language": "EN-US", "supports_formality": false}
+            ]));
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .json_body_partial(r#"{"target_lang":"DE","formality":"more","context":"UI"}"#);
+            then.status(200)
+                .json_body(json!({"translations": [{"text": "Hallo"}]}));
+        });
+        let back = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .json_body(json!({"text": ["Hallo"], "target_lang": "EN", "source_lang": "DE"}));
+            then.status(200)
+                .json_body(json!({"translations": [{"text": "Hello"}]}));
+        });
+
+        let api = DeeplApi {
+            client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
+            api_key: "k".into(),
+            base_url: server.base_url(),
+        };
+        let options = DeeplOptions {
+            formality: Some(Formality::More),
+            context: Some("UI".into()),
+            ..Default::default()
+        };
+        let chain = Chain::new(vec![("deepl".into(), Ok(Backend::deepl(api, options)))]).unwrap();
+        let forward = chain
+            .translate_streaming("Hello", "EN", "DE", None, None)
+            .await
+            .unwrap();
+        let result = back_translate(&chain, "Hello", "EN", &forward, 0.5, None)
+            .await
+            .unwrap();
+        assert_eq!(result.text, "Hello");
+        assert!(!result.below_threshold);
+        back.assert_hits(1);
+        languages.assert_hits(1);
+    }
+
+    #[test]
+    fn similarity_ignores_case_and_punctuation() {
+        assert_eq!(similarity("Hello, world!", "hello world"), 1.0);
+        assert!(similarity("Open the door", "Open the gate") > 0.6);
+        assert!(similarity("Open the door", "Banana bread recipe") < 0.4);
+        assert_eq!(similarity("", ""), 1.0);
+    }
+}
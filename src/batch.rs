@@ -0,0 +1,548 @@
+use anyhow::{Context, Result, bail};
+use futures::future::try_join_all;
+use futures::stream::{self, StreamExt};
+use serde_json::json;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::backend::Chain;
+use crate::cli::{BatchArgs, BatchFormat};
+use crate::ocr::{Ocr, ocr_image};
+use crate::protect::Protection;
+use crate::translate::Translation;
+
+/// DeepL takes at most 50 texts and 128 KiB per request; the byte limit
+/// leaves room for the JSON around the texts.
+const MAX_TEXTS: usize = 50;
+const MAX_BYTES: usize = 100 * 1024;
+
+/// What inputs may be; directories and patterns are searched for these.
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "tif", "tiff", "bmp", "gif", "webp", "pnm", "pbm", "pgm", "ppm",
+];
+
+/// The languages and OCR pack `trein batch` uses, from the main options.
+pub struct BatchJob<'a> {
+    pub src: &'a str,
+    pub targets: &'a [String],
+    pub ocr_pack: &'a str,
+    pub ocr_label: &'a str,
+    pub protect: Option<&'a Protection>,
+}
+
+/// OCRs every image with up to `--jobs` Tesseract processes, translates the
+/// texts in batches and writes each batch out before starting the next, so
+/// an interrupted run can be picked up with --resume. The backends are set
+/// up only once the inputs and output check out.
+pub async fn run_batch(
+    cmd: &BatchArgs,
+    job: &BatchJob<'_>,
+    chain: impl Future<Output = Result<Chain>>,
+) -> Result<()> {
+    let files = expand_inputs(&cmd.inputs)?;
+    if files.is_empty() {
+        bail!("No images found in {}", cmd.inputs.join(" "));
+    }
+    let mut sink = Sink::open(cmd, job.targets)?;
+    let todo: Vec<PathBuf> = files.iter().filter(|f| !sink.is_done(f)).cloned().collect();
+    let skipped = files.len() - todo.len();
+    if skipped > 0 {
+        eprintln!(
+            "Resuming: {skipped} of {} image(s) already done",
+            files.len()
+        );
+    }
+    if todo.is_empty() {
+        return Ok(());
+    }
+    let chain = chain.await?;
+
+    let jobs = cmd.jobs.unwrap_or_else(default_jobs).max(1);
+    let mut ocrs = stream::iter(todo)
+        .map(|path| async move {
+            let ocr = ocr_image(&path, job.ocr_pack, None).await;
+            (path, ocr)
+        })
+        .buffered(jobs);
+
+    let (mut done, mut failed) = (0, 0);
+    let mut pending: Vec<(PathBuf, Ocr)> = Vec::new();
+    while let Some((path, ocr)) = ocrs.next().await {
+        match ocr {
+            Ok(ocr) => pending.push((path, ocr)),
+            Err(e) => {
+                eprintln!("(Warning) {}: {e:#}", path.display());
+                sink.write_error(&path, &e)?;
+                failed += 1;
+            }
+        }
+        let bytes: usize = pending.iter().map(|(_, o)| o.text.len()).sum();
+        if pending.len() >= MAX_TEXTS || bytes >= MAX_BYTES {
+            done += flush(&mut pending, job, &chain, &mut sink).await?;
+        }
+    }
+    done += flush(&mut pending, job, &chain, &mut sink).await?;
+
+    eprintln!("Translated {done} image(s), {failed} failed, {skipped} skipped.");
+    if failed > 0 {
+        bail!("{failed} image(s) could not be read; see the warnings above");
+    }
+    Ok(())
+}
+
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Translates the pending images and writes them out.
+async fn flush(
+    pending: &mut Vec<(PathBuf, Ocr)>,
+    job: &BatchJob<'_>,
+    chain: &Chain,
+    sink: &mut Sink,
+) -> Result<usize> {
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    let translations = translate_chunk(pending, job, chain).await?;
+    for ((path, ocr), translations) in pending.iter().zip(translations) {
+        sink.write(path, ocr, job.ocr_label, &translations)?;
+    }
+    sink.flush()?;
+    Ok(std::mem::take(pending).len())
+}
+
+/// One request per target for all the texts; images without text get no
+/// translations.
+async fn translate_chunk(
+    chunk: &[(PathBuf, Ocr)],
+    job: &BatchJob<'_>,
+    chain: &Chain,
+) -> Result<Vec<Vec<Translation>>> {
+    let texts: Vec<&str> = chunk
+        .iter()
+        .map(|(_, o)| o.text.as_str())
+        .filter(|t| !t.trim().is_empty())
+        .collect();
+    let per_target = match texts.is_empty() {
+        true => Vec::new(),
+        false => {
+            try_join_all(
+                job.targets
+                    .iter()
+                    .map(|tgt| chain.translate_many(&texts, job.src, tgt, job.protect)),
+            )
+            .await?
+        }
+    };
+    let mut out = Vec::with_capacity(chunk.len());
+    let mut next = 0;
+    for (_, ocr) in chunk {
+        if ocr.text.trim().is_empty() {
+            out.push(Vec::new());
+            continue;
+        }
+        out.push(per_target.iter().map(|ts| ts[next].clone()).collect());
+        next += 1;
+    }
+    Ok(out)
+}
+
+/// Files, directories (recursively, images only) and glob patterns, in
+/// order and without duplicates.
+fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            files.extend(
+                WalkDir::new(path)
+                    .sort_by_file_name()
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .filter(|e| e.file_type().is_file() && is_image(e.path()))
+                    .map(|e| e.into_path()),
+            );
+        } else if path.is_file() {
+            if !is_image(path) {
+                bail!("{input} isn't an image ({})", IMAGE_EXTENSIONS.join(", "));
+            }
+            files.push(path.to_path_buf());
+        } else if input.contains(['*', '?', '[']) {
+            let matches: Vec<PathBuf> = glob::glob(input)
+                .with_context(|| format!("Invalid pattern {input}"))?
+                .filter_map(Result::ok)
+                .filter(|p| p.is_file() && is_image(p))
+                .collect();
+            if matches.is_empty() {
+                bail!("No images match {input}");
+            }
+            files.extend(matches);
+        } else {
+            bail!("{input}: no such file or directory");
+        }
+    }
+    let mut seen = HashSet::new();
+    files.retain(|f| seen.insert(f.clone()));
+    Ok(files)
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// `shot.png` → `shot.png.txt`, or `shot.png.DE.txt` etc. with several
+/// targets; keeping the image's extension keeps `shot.jpg` apart.
+fn sidecar_paths(image: &Path, targets: &[String]) -> Vec<PathBuf> {
+    let with_suffix = |suffix: String| {
+        let mut name = image.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+    match targets {
+        [_] => vec![with_suffix(".txt".into())],
+        _ => targets
+            .iter()
+            .map(|t| with_suffix(format!(".{t}.txt")))
+            .collect(),
+    }
+}
+
+/// Where results go. NDJSON and CSV are appended to as batches finish.
+struct Sink {
+    format: BatchFormat,
+    out: Option<Box<dyn Write>>,
+    targets: Vec<String>,
+    resume: bool,
+    /// Images recorded without an error by an earlier run.
+    done: HashSet<String>,
+}
+
+impl Sink {
+    fn open(cmd: &BatchArgs, targets: &[String]) -> Result<Self> {
+        let mut sink = Sink {
+            format: cmd.format,
+            out: None,
+            targets: targets.to_vec(),
+            resume: cmd.resume,
+            done: HashSet::new(),
+        };
+        let header = csv_header(targets);
+        match (cmd.format, &cmd.output) {
+            (BatchFormat::Sidecar, Some(_)) => {
+                bail!("--output doesn't apply to sidecar files; they go next to each image")
+            }
+            (BatchFormat::Sidecar, None) => {}
+            (_, None) if cmd.resume => {
+                bail!("--resume needs --output: the earlier results are read from that file")
+            }
+            (_, None) => {
+                sink.out = Some(Box::new(std::io::stdout()));
+                if cmd.format == BatchFormat::Csv {
+                    sink.write_line(&header)?;
+                }
+            }
+            (format, Some(path)) => {
+                let existing = match cmd.resume {
+                    true => fs::read_to_string(path).unwrap_or_default(),
+                    false => String::new(),
+                };
+                let kept = match format {
+                    BatchFormat::Csv => parse_csv(&existing).1,
+                    _ => existing.rfind('\n').map_or(0, |i| i + 1),
+                };
+                let existing = &existing[..kept];
+                if format == BatchFormat::Csv
+                    && let Some(first) = existing.lines().next()
+                    && first != header
+                {
+                    bail!(
+                        "{} has the columns `{first}`; resume it with the same targets",
+                        path.display()
+                    );
+                }
+                if format == BatchFormat::Ndjson
+                    && let Some(other) = ndjson_other_targets(existing, targets)
+                {
+                    bail!(
+                        "{} was written for the targets {}; resume it with the same targets",
+                        path.display(),
+                        other.join(",")
+                    );
+                }
+                sink.done = match format {
+                    BatchFormat::Csv => csv_done(existing),
+                    _ => ndjson_done(existing),
+                };
+                let file = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(cmd.resume)
+                    .truncate(!cmd.resume)
+                    .open(path)
+                    .with_context(|| format!("Could not open {}", path.display()))?;
+                // Drops a record cut short by the interruption.
+                if cmd.resume {
+                    file.set_len(kept as u64)
+                        .with_context(|| format!("Could not write {}", path.display()))?;
+                }
+                sink.out = Some(Box::new(file));
+                if format == BatchFormat::Csv && existing.is_empty() {
+                    sink.write_line(&header)?;
+                }
+            }
+        }
+        Ok(sink)
+    }
+
+    fn is_done(&self, image: &Path) -> bool {
+        match self.format {
+            BatchFormat::Sidecar => {
+                self.resume
+                    && sidecar_paths(image, &self.targets)
+                        .iter()
+                        .all(|p| p.exists())
+            }
+            _ => self.done.contains(&image.display().to_string()),
+        }
+    }
+
+    fn write(
+        &mut self,
+        image: &Path,
+        ocr: &Ocr,
+        ocr_label: &str,
+        translations: &[Translation],
+    ) -> Result<()> {
+        let file = image.display().to_string();
+        match self.format {
+            BatchFormat::Ndjson => {
+                let record = json!({
+                    "file": file,
+                    "targets": self.targets,
+                    "ocr": { "lang": ocr_label, "text": ocr.text, "confidence": ocr.confidence },
+                    "translations": translations,
+                });
+                self.write_line(&record.to_string())
+            }
+            BatchFormat::Csv => {
+                let mut row = vec![file.as_str(), ocr.text.as_str()];
+                for target in &self.targets {
+                    let text = translations
+                        .iter()
+                        .find(|t| &t.target == target)
+                        .map_or("", |t| t.text.as_str());
+                    row.push(text);
+                }
+                row.push("");
+                self.write_line(&csv_row(&row))
+            }
+            BatchFormat::Sidecar => {
+                for (path, target) in sidecar_paths(image, &self.targets)
+                    .iter()
+                    .zip(&self.targets)
+                {
+                    let text = translations
+                        .iter()
+                        .find(|t| &t.target == target)
+                        .map_or("", |t| t.text.as_str());
+                    fs::write(path, format!("{text}\n"))
+                        .with_context(|| format!("Could not write {}", path.display()))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Failed images are recorded but not counted as done, so --resume
+    /// tries them again.
+    fn write_error(&mut self, image: &Path, err: &anyhow::Error) -> Result<()> {
+        let file = image.display().to_string();
+        let error = format!("{err:#}");
+        match self.format {
+            BatchFormat::Ndjson => {
+                let record = json!({ "file": file, "targets": self.targets, "error": error });
+                self.write_line(&record.to_string())
+            }
+            BatchFormat::Csv => {
+                let mut row = vec![file.as_str(), ""];
+                row.extend(self.targets.iter().map(|_| ""));
+                row.push(&error);
+                let line = csv_row(&row);
+                self.write_line(&line)
+            }
+            BatchFormat::Sidecar => Ok(()),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        if let Some(out) = &mut self.out {
+            writeln!(out, "{line}").context("Could not write the results")?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(out) = &mut self.out {
+            out.flush().context("Could not write the results")?;
+        }
+        Ok(())
+    }
+}
+
+fn ndjson_done(existing: &str) -> HashSet<String> {
+    existing
+        .lines()
+        .filter_map(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+        .filter(|v| v.get("error").is_none())
+        .filter_map(|v| v["file"].as_str().map(str::to_string))
+        .collect()
+}
+
+/// The targets of the first earlier record written for different ones.
+fn ndjson_other_targets(existing: &str, targets: &[String]) -> Option<Vec<String>> {
+    existing
+        .lines()
+        .filter_map(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+        .filter_map(|v| serde_json::from_value::<Vec<String>>(v["targets"].clone()).ok())
+        .find(|t| t != targets)
+}
+
+fn csv_done(existing: &str) -> HashSet<String> {
+    parse_csv(existing)
+        .0
+        .into_iter()
+        .skip(1)
+        .filter(|row| row.last().is_some_and(|e| e.is_empty()))
+        .filter_map(|row| row.into_iter().next())
+        .collect()
+}
+
+fn csv_header(targets: &[String]) -> String {
+    let mut columns = vec!["file", "text"];
+    columns.extend(targets.iter().map(String::as_str));
+    columns.push("error");
+    csv_row(&columns)
+}
+
+/// RFC 4180: fields with commas, quotes or line breaks are quoted.
+fn csv_row(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|f| match f.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", f.replace('"', "\"\"")),
+            false => f.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Reads back what `csv_row` writes, with the length of the complete rows;
+/// an unterminated last row is dropped.
+fn parse_csv(text: &str) -> (Vec<Vec<String>>, usize) {
+    let mut rows = Vec::new();
+    let (mut row, mut field) = (Vec::new(), String::new());
+    let mut quoted = false;
+    let mut complete = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek().is_some_and(|&(_, c)| c == '"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                complete = i + 1;
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    (rows, complete)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_expand_directories_and_globs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir(dir.join("sub")).unwrap();
+        for name in ["b.png", "a.JPG", "notes.txt", "sub/c.tif"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let root = dir.display().to_string();
+
+        let files = expand_inputs(&[root.clone(), format!("{root}/b.png")]).unwrap();
+        let names: Vec<_> = files.iter().map(|f| f.strip_prefix(dir).unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                Path::new("a.JPG"),
+                Path::new("b.png"),
+                Path::new("sub/c.tif")
+            ]
+        );
+
+        let files = expand_inputs(&[format!("{root}/*.png")]).unwrap();
+        assert_eq!(files, [dir.join("b.png")]);
+        assert!(expand_inputs(&[format!("{root}/*.bmp")]).is_err());
+        assert!(expand_inputs(&[format!("{root}/missing.png")]).is_err());
+        assert!(expand_inputs(&[format!("{root}/notes.txt")]).is_err());
+    }
+
+    #[test]
+    fn csv_rows_round_trip_and_resume_skips_errors() {
+        let targets = vec!["DE".to_string(), "PL".to_string()];
+        let text = [
+            csv_header(&targets),
+            csv_row(&["a.png", "Hi, \"you\"", "Hallo,\ndu", "Cześć", ""]),
+            csv_row(&["b.png", "", "", "", "Tesseract failed"]),
+            "c.png,\"cut\nsho".to_string(),
+        ]
+        .join("\n");
+        let (rows, complete) = parse_csv(&text);
+        assert_eq!(rows[0], ["file", "text", "DE", "PL", "error"]);
+        assert_eq!(rows[1][1], "Hi, \"you\"");
+        assert_eq!(rows[1][2], "Hallo,\ndu");
+        assert_eq!(rows.len(), 3);
+        assert!(text[..complete].ends_with("Tesseract failed\n"));
+        assert_eq!(csv_done(&text), HashSet::from(["a.png".to_string()]));
+
+        let ndjson = "{\"file\":\"a.png\",\"targets\":[\"DE\"],\"translations\":[]}\n\
+                      {\"file\":\"b.png\",\"error\":\"x\"}\n{\"fi";
+        assert_eq!(ndjson_done(ndjson), HashSet::from(["a.png".to_string()]));
+        assert_eq!(ndjson_other_targets(ndjson, &["DE".to_string()]), None);
+        assert_eq!(
+            ndjson_other_targets(ndjson, &targets),
+            Some(vec!["DE".to_string()])
+        );
+    }
+
+    #[test]
+    fn sidecars_name_the_target_when_there_are_several() {
+        let one = sidecar_paths(Path::new("shots/a.png"), &["EN".into()]);
+        assert_eq!(one, [PathBuf::from("shots/a.png.txt")]);
+        let two = sidecar_paths(Path::new("a.jpg"), &["EN-GB".into(), "DE".into()]);
+        assert_eq!(
+            two,
+            [
+                PathBuf::from("a.jpg.EN-GB.txt"),
+                PathBuf::from("a.jpg.DE.txt")
+            ]
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tempfile::{TempDir, tempdir};
+
+use crate::cli::SelectMode;
+use crate::region::Geometry;
+use crate::timeouts::Stage;
+use crate::tools;
+use crate::wayland::{self, Compositor, Session};
+use crate::x11;
+
+/// Which tools select and capture the screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureBackend {
+    /// Picked from the session: grim on wlroots Wayland, maim or import on X11.
+    #[default]
+    Auto,
+    Grim,
+    Maim,
+    Import,
+    Command,
+}
+
+/// `[capture]` in config.toml.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub backend: CaptureBackend,
+    /// For `backend = "command"`: prints the selected region as "x,y WxH"
+    /// (default: slurp on Wayland, slop on X11).
+    pub select: Vec<String>,
+    /// For `backend = "command"`: writes a PNG of the region to `{output}`;
+    /// `{x}`, `{y}`, `{width}`, `{height}` and `{geometry}` describe it.
+    pub capture: Vec<String>,
+}
+
+/// Selects regions and takes screenshots of them.
+#[derive(Debug, Clone)]
+pub enum Capturer {
+    /// slurp + grim on wlroots compositors.
+    Grim(Compositor),
+    /// slop + maim on X11.
+    Maim,
+    /// slop + ImageMagick's `import` on X11.
+    Import,
+    /// External programs from the config.
+    Command {
+        select: Vec<String>,
+        capture: Vec<String>,
+    },
+}
+
+impl Capturer {
+    pub fn from_config(config: &CaptureConfig) -> Result<Self> {
+        let session = Session::detect();
+        let compositor = match session {
+            Session::Wayland(c) => c,
+            _ => Compositor::Other,
+        };
+        Ok(match config.backend {
+            CaptureBackend::Auto => match session {
+                Session::Wayland(c) if c.is_wlroots() => Capturer::Grim(c),
+                Session::Wayland(c) => bail!(
+                    "grim can't capture the screen on {c:?}. Set `backend = \"command\"` under \
+                     [capture] in config.toml with a screenshot tool that works there."
+                ),
+                Session::X11 if tools::available("maim") => Capturer::Maim,
+                Session::X11 => Capturer::Import,
+                Session::None => {
+                    bail!("No graphical session: neither $WAYLAND_DISPLAY nor $DISPLAY is set.")
+                }
+            },
+            CaptureBackend::Grim => Capturer::Grim(compositor),
+            CaptureBackend::Maim => Capturer::Maim,
+            CaptureBackend::Import => Capturer::Import,
+            CaptureBackend::Command => {
+                if config.capture.is_empty() {
+                    bail!("[capture] backend = \"command\" needs a `capture` command");
+                }
+                let select = match (config.select.is_empty(), session) {
+                    (false, _) => config.select.clone(),
+                    (true, Session::Wayland(_)) => argv(&["slurp", "-f", "%x,%y %wx%h"]),
+                    (true, _) => argv(&["slop", "-f", "%x,%y %wx%h"]),
+                };
+                Capturer::Command {
+                    select,
+                    capture: config.capture.clone(),
+                }
+            }
+        })
+    }
+
+    /// Asks the compositor (or the user, by dragging or clicking) for the
+    /// area to capture.
+    pub async fn select(&self, mode: SelectMode) -> Result<Geometry> {
+        match (self, mode) {
+            (Capturer::Grim(_), SelectMode::Region) => slurp(&[], None).await,
+            (Capturer::Grim(c), SelectMode::Window) => wayland::focused_window(*c).await,
+            (Capturer::Grim(c), SelectMode::Pick) => {
+                let windows = wayland::visible_windows(*c).await?;
+                if windows.is_empty() {
+                    bail!("Nothing to pick from");
+                }
+                slurp(&["-r"], Some(&windows)).await
+            }
+            (Capturer::Grim(c), SelectMode::Output) => match wayland::focused_output(*c).await {
+                Ok(Some(g)) => Ok(g),
+                _ => slurp(&["-o", "-r"], None).await,
+            },
+            // slop selects a window when it is clicked rather than dragged.
+            (Capturer::Maim | Capturer::Import, SelectMode::Region | SelectMode::Pick) => {
+                select_with(&argv(&["slop", "-f", "%x,%y %wx%h"]), None).await
+            }
+            (Capturer::Maim | Capturer::Import, SelectMode::Window) => x11::active_window().await,
+            (Capturer::Maim | Capturer::Import, SelectMode::Output) => x11::outputs()
+                .await?
+                .into_iter()
+                .find_map(|(g, primary)| primary.then_some(g))
+                .context("No primary monitor; use --select region"),
+            (Capturer::Command { select, .. }, SelectMode::Region | SelectMode::Pick) => {
+                select_with(select, None).await
+            }
+            (Capturer::Command { .. }, _) => {
+                bail!("The command capture backend only supports --select region")
+            }
+        }
+    }
+
+    /// Selects regions one after another until the user cancels (Esc); at
+    /// least one is required.
+    pub async fn select_many(&self, mode: SelectMode) -> Result<Vec<Geometry>> {
+        if !matches!(mode, SelectMode::Region | SelectMode::Pick) {
+            bail!("--multi needs --select region or --select pick");
+        }
+        let mut regions = vec![self.select(mode).await?];
+        loop {
+            eprintln!(
+                "Region {}: {} (select another, or press Esc to finish)",
+                regions.len(),
+                regions[regions.len() - 1]
+            );
+            match self.select(mode).await {
+                Ok(g) => regions.push(g),
+                Err(e) if e.is::<Cancelled>() => return Ok(regions),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Screenshot of `geometry` as a PNG in a temp dir that lives as long as
+    /// the returned handle.
+    pub async fn capture(&self, geometry: &Geometry) -> Result<(TempDir, PathBuf)> {
+        let tmpdir = tempdir().context("Could not create temp dir")?;
+        let png_path = tmpdir.path().join("capture.png");
+        let png = png_path.to_string_lossy().to_string();
+        let command = match self {
+            Capturer::Grim(_) => argv(&["grim", "-g", &geometry.to_string(), &png]),
+            Capturer::Maim => argv(&["maim", "-g", &x11::x_geometry(geometry), &png]),
+            Capturer::Import => argv(&[
+                "import",
+                "-window",
+                "root",
+                "-crop",
+                &x11::x_geometry(geometry),
+                &png,
+            ]),
+            Capturer::Command { capture, .. } => expand(capture, geometry, &png_path),
+        };
+        let (program, args) = command.split_first().context("Empty capture command")?;
+        let mut capture = tools::command(program);
+        capture.args(args);
+        let out = tools::run(capture, program, None, Stage::Capture).await?;
+        if !out.status.success() {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            bail!(
+                "`{program}` failed to capture the region: {}",
+                stderr.trim()
+            );
+        }
+        if !png_path.exists() {
+            bail!("`{program}` did not write {}", png_path.display());
+        }
+        Ok((tmpdir, png_path))
+    }
+
+    /// Areas of the current outputs, when the backend can list them.
+    pub async fn outputs(&self) -> Result<Option<Vec<Geometry>>> {
+        let outputs = match self {
+            Capturer::Grim(_) => wayland::current_outputs().await?,
+            Capturer::Maim | Capturer::Import => {
+                x11::outputs().await?.into_iter().map(|(g, _)| g).collect()
+            }
+            Capturer::Command { .. } => return Ok(None),
+        };
+        Ok(Some(outputs))
+    }
+}
+
+fn argv(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+/// Fills in the `capture` command's placeholders.
+fn expand(template: &[String], g: &Geometry, output: &Path) -> Vec<String> {
+    template
+        .iter()
+        .map(|arg| {
+            arg.replace("{x}", &g.x.to_string())
+                .replace("{y}", &g.y.to_string())
+                .replace("{width}", &g.width.to_string())
+                .replace("{height}", &g.height.to_string())
+                .replace("{geometry}", &g.to_string())
+                .replace("{output}", &output.to_string_lossy())
+        })
+        .collect()
+}
+
+/// The selector exited without a selection: the user pressed Esc.
+#[derive(Debug)]
+pub struct Cancelled(pub String);
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Selection cancelled (`{}` printed nothing).", self.0)
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+async fn slurp(args: &[&str], boxes: Option<&[Geometry]>) -> Result<Geometry> {
+    let mut command = vec!["slurp".to_string()];
+    command.extend(argv(args));
+    command.extend(argv(&["-f", "%x,%y %wx%h"]));
+    select_with(&command, boxes).await
+}
+
+/// Runs a selector that prints "x,y WxH", offering `boxes` on stdin.
+async fn select_with(command: &[String], boxes: Option<&[Geometry]>) -> Result<Geometry> {
+    let (program, args) = command.split_first().context("Empty select command")?;
+    let mut select = tools::command(program);
+    select.args(args).stderr(Stdio::inherit());
+    let list: Option<String> = boxes.map(|b| b.iter().map(|b| format!("{b}\n")).collect());
+    let out = tools::run(
+        select,
+        program,
+        list.as_deref().map(str::as_bytes),
+        Stage::Select,
+    )
+    .await?;
+
+    let geometry = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    if !out.status.success() {
+        if geometry.is_empty() {
+            return Err(Cancelled(program.clone()).into());
+        }
+        bail!("`{program}` failed ({}): {geometry}", out.status);
+    }
+    if geometry.is_empty() {
+        bail!("No selection geometry received from `{program}`.");
+    }
+    geometry.parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_config_and_command_template() {
+        let config: CaptureConfig = toml::from_str(
+            r#"
+            backend = "command"
+            capture = ["shot", "--area", "{x},{y},{width},{height}", "-o", "{output}"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.backend, CaptureBackend::Command);
+        assert!(config.select.is_empty());
+
+        let g: Geometry = "-10,20 300x40".parse().unwrap();
+        assert_eq!(
+            expand(&config.capture, &g, Path::new("/tmp/x/capture.png")),
+            [
+                "shot",
+                "--area",
+                "-10,20,300,40",
+                "-o",
+                "/tmp/x/capture.png"
+            ]
+        );
+        assert_eq!(CaptureConfig::default().backend, CaptureBackend::Auto);
+    }
+
+    #[test]
+    fn command_backend_needs_a_capture_command() {
+        let config = CaptureConfig {
+            backend: CaptureBackend::Command,
+            ..CaptureConfig::default()
+        };
+        assert!(Capturer::from_config(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn multi_selection_stops_only_on_cancel() {
+        let dir = tempfile::tempdir().unwrap();
+        let count = dir.path().join("count");
+        // Selects once, then runs `second` on every later call.
+        let capturer = |second: &str| Capturer::Command {
+            select: argv(&[
+                "sh",
+                "-c",
+                &format!(
+                    "if [ -e {0} ]; then {second}; else touch {0}; echo '1,2 3x4'; fi",
+                    count.display()
+                ),
+            ]),
+            capture: Vec::new(),
+        };
+
+        let regions = capturer("exit 1")
+            .select_many(SelectMode::Region)
+            .await
+            .unwrap();
+        assert_eq!(regions.len(), 1);
+
+        std::fs::remove_file(&count).unwrap();
+        let err = capturer("echo oops; exit 1")
+            .select_many(SelectMode::Region)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("oops"), "{err}");
+
+        std::fs::remove_file(&count).unwrap();
+        assert!(
+            capturer("echo garbage")
+                .select_many(SelectMode::Region)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -1,8 +1,15 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+use crate::region::Geometry;
+use crate::translate::{Formality, ModelType, SplitSentences};
 
 #[derive(Parser, Debug)]
 #[command(name = "trein", version, about = "Select area → OCR → DeepL translate")]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Source language code: "AR" | "BG" | "CS" | "DA" | "DE" | "EL" | "EN" | "ES" | "ET" | "FI" | "FR" | "HE"
     /// | "HU" | "ID" | "IT" | "JA" | "KO" | "LT" | "LV" | "NB" | "NL" | "PL" | "PT" | "RO"
     /// | "RU" | "SK" | "SL" | "SV" | "TH" | "TR" | "UK" | "VI" | "ZH"
@@ -13,13 +20,143 @@ pub struct Args {
     /// | "ET" | "FI" | "FR" | "HE" | "HU" | "ID" | "IT" | "JA" | "KO" | "LT" | "LV" | "NB"
     /// | "NL" | "PL" | "PT" | "PT-BR" | "PT-PT" | "RO" | "RU" | "SK" | "SL" | "SV" | "TH"
     /// | "TR" | "UK" | "VI" | "ZH" | "ZH-HANS" | "ZH-HANT"
-    #[arg(short = 't', long = "target-lang", default_value = "EN")]
-    pub target_lang: String,
+    /// Several targets may be given comma-separated (e.g. EN,PL,DE); the first is the primary.
+    #[arg(
+        short = 't',
+        long = "target-lang",
+        default_value = "EN",
+        value_delimiter = ','
+    )]
+    pub target_lang: Vec<String>,
 
     /// Also copy the translation to the Wayland clipboard using wl-copy (if available).
+    /// Only the primary target is copied unless --copy-all is given.
     #[arg(short = 'c', long = "copy")]
     pub copy: bool,
 
+    /// With --copy, copy every target's translation instead of only the primary one.
+    #[arg(long = "copy-all", requires = "copy", conflicts_with = "compare")]
+    pub copy_all: bool,
+
+    /// Backends to try in order, falling through on quota/auth/network errors.
+    /// Overrides `chain` in config.toml.
+    #[arg(long = "backend", value_name = "NAMES", value_delimiter = ',')]
+    pub backend: Option<Vec<String>>,
+
+    /// Refuse every backend that needs the network (DeepL, remote servers).
+    #[arg(long = "offline", global = true)]
+    pub offline: bool,
+
+    /// Run the text through several backends side by side ("deepl" is the main key,
+    /// others come from [backends.NAME] in config.toml). Without names, uses all of them.
+    #[arg(long = "compare", value_name = "NAMES", num_args = 0.., value_delimiter = ',')]
+    pub compare: Option<Vec<String>>,
+
+    /// Translate the result back into the source language and show a similarity score.
+    #[arg(short = 'b', long = "back-translate", conflicts_with = "compare")]
+    pub back_translate: bool,
+
+    /// Flag back-translations whose similarity (0–1) is below this.
+    #[arg(
+        long = "back-threshold",
+        default_value_t = 0.6,
+        requires = "back_translate"
+    )]
+    pub back_threshold: f64,
+
+    /// Output format.
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Don't echo tokens from streaming backends while they arrive.
+    #[arg(long = "no-stream")]
+    pub no_stream: bool,
+
+    /// Capture this region ("x,y WxH") instead of selecting one with slurp.
+    #[arg(
+        long = "geometry",
+        value_name = "X,Y WxH",
+        conflicts_with_all = ["last_region", "region"]
+    )]
+    pub geometry: Option<Geometry>,
+
+    /// Capture the previously used region again, skipping slurp.
+    #[arg(long = "last-region", conflicts_with = "region")]
+    pub last_region: bool,
+
+    /// Capture a region saved with `trein region save NAME`.
+    #[arg(long = "region", value_name = "NAME")]
+    pub region: Option<String>,
+
+    /// How to select the capture area when no region is given.
+    #[arg(
+        long = "select",
+        value_enum,
+        default_value_t = SelectMode::Region,
+        conflicts_with_all = ["geometry", "last_region", "region"]
+    )]
+    pub select: SelectMode,
+
+    /// Select several regions one after another (Esc ends) and translate each.
+    #[arg(
+        short = 'm',
+        long = "multi",
+        conflicts_with_all = ["geometry", "last_region", "region", "watch", "compare"]
+    )]
+    pub multi: bool,
+
+    /// With --multi, translate the regions as one text in selection order.
+    #[arg(long = "join", requires = "multi")]
+    pub join: bool,
+
+    /// Fix the OCR text before translating ($VISUAL/$EDITOR, else inline).
+    #[arg(short = 'e', long = "edit", conflicts_with = "watch")]
+    pub edit: bool,
+
+    /// Show the OCR text and ask before translating it.
+    #[arg(long = "confirm", conflicts_with = "watch")]
+    pub confirm: bool,
+
+    /// Stop after OCR: print the text and the characters DeepL would bill.
+    #[arg(
+        long = "dry-run",
+        visible_alias = "ocr-only",
+        conflicts_with_all = ["watch", "compare", "confirm"]
+    )]
+    pub dry_run: bool,
+
+    /// Also save the captured image to this file.
+    #[arg(long = "save-image", value_name = "PATH", conflicts_with_all = ["watch", "multi"])]
+    pub save_image: Option<PathBuf>,
+
+    /// Write the capture, Tesseract's input and output and the translation
+    /// requests into this directory, for debugging.
+    #[arg(long = "keep-artifacts", value_name = "DIR", conflicts_with = "watch")]
+    pub keep_artifacts: Option<PathBuf>,
+
+    /// Keep re-capturing the region and translate whenever its text changes.
+    #[arg(short = 'w', long = "watch", conflicts_with = "compare")]
+    pub watch: bool,
+
+    /// Seconds between captures in --watch mode.
+    #[arg(
+        long = "interval",
+        value_name = "SECS",
+        default_value_t = 1.0,
+        value_parser = positive_seconds,
+        requires = "watch"
+    )]
+    pub interval: f64,
+
+    /// Captures the new text must stay unchanged before it is translated in --watch mode.
+    #[arg(
+        long = "debounce",
+        value_name = "POLLS",
+        default_value_t = 2,
+        requires = "watch"
+    )]
+    pub debounce: u32,
+
     /// Optional override for the Tesseract language if you need to force it (e.g., chi_tra).
     /// Normally derived from --source-lang.
     #[arg(long = "ocr-lang")]
@@ -27,8 +164,171 @@ pub struct Args {
 
     /// DeepL API key. If omitted, falls back to $DEEPL_API_KEY, then config files.
     /// NOTE: requires clap feature `env`. If you don't enable it, remove `env = ...` here.
-    #[arg(long = "deepl-api-key", env = "DEEPL_API_KEY")]
+    #[arg(long = "deepl-api-key", env = "DEEPL_API_KEY", global = true)]
     pub deepl_api_key: Option<String>,
+
+    /// DeepL endpoint: "free", "pro", or a base URL. Defaults to $DEEPL_API_BASE,
+    /// then Free for keys ending in ":fx" and Pro otherwise.
+    #[arg(long = "deepl-endpoint", value_name = "free|pro|URL", global = true)]
+    pub deepl_endpoint: Option<String>,
+
+    /// Config profile to take defaults from ([profiles.NAME] in config.toml).
+    #[arg(short = 'p', long = "profile")]
+    pub profile: Option<String>,
+
+    /// DeepL formality. The prefer_* variants fall back silently for unsupported languages.
+    #[arg(long = "formality", value_enum)]
+    pub formality: Option<Formality>,
+
+    /// Extra context for DeepL (not translated), e.g. surrounding text or a hint.
+    #[arg(long = "context")]
+    pub context: Option<String>,
+
+    /// DeepL sentence splitting.
+    #[arg(long = "split-sentences", value_enum)]
+    pub split_sentences: Option<SplitSentences>,
+
+    /// Ask DeepL to keep the original punctuation and casing.
+    #[arg(long = "preserve-formatting", num_args = 0..=1, default_missing_value = "true")]
+    pub preserve_formatting: Option<bool>,
+
+    /// DeepL glossary (name or ID) to apply; its language pair must match.
+    #[arg(short = 'g', long = "glossary")]
+    pub glossary: Option<String>,
+
+    /// Don't mask URLs, e-mails, versions, identifiers and local glossary terms before translating.
+    #[arg(long = "no-protect")]
+    pub no_protect: bool,
+
+    /// DeepL model type.
+    #[arg(long = "model-type", value_enum)]
+    pub model_type: Option<ModelType>,
+
+    /// How many times to retry DeepL on 429/5xx or connection errors (default: 3, or [retry] in config).
+    #[arg(long = "retries")]
+    pub retries: Option<u32>,
+
+    /// Give up on DeepL after this many seconds, retries included (default: 30, or [retry] in config).
+    #[arg(long = "retry-deadline", value_name = "SECS")]
+    pub retry_deadline: Option<u64>,
+
+    /// Print diagnostics (e.g. which DeepL endpoint is used) to stderr.
+    #[arg(short = 'v', long = "verbose", global = true)]
+    pub verbose: bool,
+}
+
+fn positive_seconds(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs > 0.0 => Ok(secs),
+        _ => Err(format!("`{s}` is not a positive number of seconds")),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SelectMode {
+    /// Drag a rectangle with slurp.
+    Region,
+    /// The focused window.
+    Window,
+    /// Click one of the visible windows.
+    Pick,
+    /// The focused output (or click one where the compositor can't tell).
+    Output,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage DeepL glossaries.
+    #[command(subcommand)]
+    Glossary(GlossaryCommand),
+    /// Manage saved screen regions.
+    #[command(subcommand)]
+    Region(RegionCommand),
+    /// OCR and translate image files instead of the screen.
+    Batch(BatchArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct BatchArgs {
+    /// Images, directories (searched recursively) or quoted glob patterns.
+    #[arg(required = true, value_name = "PATH")]
+    pub inputs: Vec<String>,
+
+    /// OCR this many images at once (default: number of CPUs).
+    #[arg(short = 'j', long = "jobs", value_name = "N")]
+    pub jobs: Option<usize>,
+
+    /// How to write the results.
+    #[arg(long = "format", value_enum, default_value_t = BatchFormat::Ndjson)]
+    pub format: BatchFormat,
+
+    /// Write NDJSON or CSV here instead of stdout.
+    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Skip images already in the output (or with sidecar files) from an
+    /// earlier, interrupted run.
+    #[arg(long = "resume")]
+    pub resume: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BatchFormat {
+    /// One JSON object per image.
+    Ndjson,
+    /// One row per image, one column per target.
+    Csv,
+    /// A `.txt` file next to each image.
+    Sidecar,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RegionCommand {
+    /// Save a region under a name (selected with slurp unless given).
+    Save {
+        name: String,
+        /// Use this region ("x,y WxH") instead of selecting one.
+        #[arg(long = "geometry", value_name = "X,Y WxH", conflicts_with = "last")]
+        geometry: Option<Geometry>,
+        /// Save the last captured region.
+        #[arg(long = "last")]
+        last: bool,
+        /// How to select the region when neither is given.
+        #[arg(long = "select", value_enum, default_value_t = SelectMode::Region)]
+        select: SelectMode,
+    },
+    /// List saved regions.
+    List,
+    /// Delete a saved region.
+    Delete { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GlossaryCommand {
+    /// Upload a glossary from a TSV or CSV file (source<TAB>target per line).
+    Create {
+        name: String,
+        /// Source language, e.g. EN.
+        #[arg(long = "from")]
+        source_lang: String,
+        /// Target language, e.g. DE.
+        #[arg(long = "to")]
+        target_lang: String,
+        /// Entries file; `.csv` is sent as CSV, anything else as TSV.
+        file: PathBuf,
+    },
+    /// List glossaries on the DeepL account.
+    List,
+    /// Show a glossary's details and entries.
+    Show { name: String },
+    /// Delete a glossary by name or ID.
+    Delete { name: String },
 }
 
 #[cfg(test)]
@@ -40,11 +340,39 @@ mod tests {
     fn parses_defaults() {
         let args = Args::parse_from(["trein"]);
         assert_eq!(args.source_lang, "EN");
-        assert_eq!(args.target_lang, "EN");
+        assert_eq!(args.target_lang, vec!["EN"]);
+        assert_eq!(args.format, OutputFormat::Text);
+        assert!(!args.no_stream);
+        assert!(args.geometry.is_none());
+        assert!(!args.last_region);
+        assert_eq!(args.select, SelectMode::Region);
+        assert!(!args.multi && !args.join);
+        assert!(args.save_image.is_none() && args.keep_artifacts.is_none());
+        assert!(!args.edit && !args.confirm && !args.dry_run);
+        assert!(args.region.is_none());
+        assert!(!args.watch);
+        assert_eq!(args.interval, 1.0);
+        assert_eq!(args.debounce, 2);
+        assert!(!args.copy_all);
+        assert!(args.compare.is_none());
+        assert!(args.backend.is_none());
+        assert!(!args.back_translate);
+        assert_eq!(args.back_threshold, 0.6);
         assert!(!args.copy);
         assert!(args.ocr_lang.is_none());
         // deepl_api_key is None unless provided
         assert!(args.deepl_api_key.is_none());
+        assert!(args.retries.is_none());
+        assert!(args.retry_deadline.is_none());
+        assert!(args.deepl_endpoint.is_none());
+        assert!(!args.verbose);
+        assert!(args.profile.is_none());
+        assert!(args.formality.is_none());
+        assert!(args.preserve_formatting.is_none());
+        assert!(args.glossary.is_none());
+        assert!(!args.no_protect);
+        assert!(!args.offline);
+        assert!(args.command.is_none());
     }
 
     #[test]
@@ -62,9 +390,148 @@ mod tests {
             "k123",
         ]);
         assert_eq!(args.source_lang, "JA");
-        assert_eq!(args.target_lang, "EN-GB");
+        assert_eq!(args.target_lang, vec!["EN-GB"]);
         assert!(args.copy);
         assert_eq!(args.ocr_lang.as_deref(), Some("chi_tra"));
         assert_eq!(args.deepl_api_key.as_deref(), Some("k123"));
     }
+
+    #[test]
+    fn parses_multiple_targets() {
+        let args = Args::parse_from([
+            "trein",
+            "-t",
+            "EN,PL",
+            "-t",
+            "DE",
+            "--copy",
+            "--copy-all",
+            "-f",
+            "json",
+        ]);
+        assert_eq!(args.target_lang, vec!["EN", "PL", "DE"]);
+        assert!(args.copy_all);
+        assert_eq!(args.format, OutputFormat::Json);
+    }
+
+    #[test]
+    fn parses_compare() {
+        let args = Args::parse_from(["trein", "--compare"]);
+        assert_eq!(args.compare, Some(vec![]));
+        let args = Args::parse_from(["trein", "--compare", "deepl,pro"]);
+        assert_eq!(args.compare, Some(vec!["deepl".into(), "pro".into()]));
+        // --compare copies only the primary target's first result.
+        assert!(Args::try_parse_from(["trein", "--compare", "-c", "--copy-all"]).is_err());
+        assert!(Args::try_parse_from(["trein", "--compare", "-b"]).is_err());
+    }
+
+    #[test]
+    fn parses_geometry_and_last_region() {
+        let args = Args::parse_from(["trein", "--geometry", "10,20 640x120"]);
+        assert_eq!(args.geometry.unwrap().to_string(), "10,20 640x120");
+        assert!(Args::try_parse_from(["trein", "--geometry", "10,20"]).is_err());
+        assert!(Args::try_parse_from(["trein", "--geometry", "1,2 3x4", "--last-region"]).is_err());
+        assert!(Args::try_parse_from(["trein", "--region", "dialog", "--last-region"]).is_err());
+        assert!(Args::try_parse_from(["trein", "--select", "window", "--last-region"]).is_err());
+        let args = Args::parse_from(["trein", "--select", "output"]);
+        assert_eq!(args.select, SelectMode::Output);
+
+        let args = Args::parse_from(["trein", "-m", "--join", "--select", "pick"]);
+        assert!(args.multi && args.join);
+        assert!(Args::try_parse_from(["trein", "--join"]).is_err());
+        assert!(Args::try_parse_from(["trein", "--multi", "--last-region"]).is_err());
+        assert!(Args::try_parse_from(["trein", "-m", "--save-image", "a.png"]).is_err());
+        assert!(Args::try_parse_from(["trein", "-m", "--keep-artifacts", "dbg"]).is_ok());
+        assert!(Args::try_parse_from(["trein", "-e", "--confirm"]).is_ok());
+        assert!(Args::try_parse_from(["trein", "-w", "--confirm"]).is_err());
+        assert!(Args::parse_from(["trein", "--ocr-only", "-m"]).dry_run);
+        assert!(Args::try_parse_from(["trein", "--dry-run", "--compare"]).is_err());
+
+        let args = Args::parse_from(["trein", "region", "save", "dialog", "--last"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Region(RegionCommand::Save { ref name, last: true, .. })) if name == "dialog"
+        ));
+    }
+
+    #[test]
+    fn interval_must_be_positive_and_finite() {
+        let args = Args::parse_from(["trein", "-w", "--interval", "0.5"]);
+        assert_eq!(args.interval, 0.5);
+        for bad in ["-1", "0", "nan", "inf", "soon"] {
+            assert!(
+                Args::try_parse_from(["trein", "-w", "--interval", bad]).is_err(),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_deepl_options() {
+        let args = Args::parse_from([
+            "trein",
+            "--profile",
+            "work",
+            "--formality",
+            "prefer-more",
+            "--split-sentences",
+            "nonewlines",
+            "--preserve-formatting",
+            "--model-type",
+            "quality-optimized",
+        ]);
+        assert_eq!(args.profile.as_deref(), Some("work"));
+        assert_eq!(args.formality, Some(Formality::PreferMore));
+        assert_eq!(args.split_sentences, Some(SplitSentences::Nonewlines));
+        assert_eq!(args.preserve_formatting, Some(true));
+        assert_eq!(args.model_type, Some(ModelType::QualityOptimized));
+    }
+
+    #[test]
+    fn parses_glossary_subcommands() {
+        let args = Args::parse_from([
+            "trein", "glossary", "create", "ui", "--from", "ja", "--to", "de", "ui.tsv",
+        ]);
+        match args.command {
+            Some(Command::Glossary(GlossaryCommand::Create {
+                name,
+                source_lang,
+                target_lang,
+                file,
+            })) => {
+                assert_eq!(name, "ui");
+                assert_eq!(source_lang, "ja");
+                assert_eq!(target_lang, "de");
+                assert_eq!(file, PathBuf::from("ui.tsv"));
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        let args = Args::parse_from(["trein", "glossary", "list", "--deepl-api-key", "k"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Glossary(GlossaryCommand::List))
+        ));
+        assert_eq!(args.deepl_api_key.as_deref(), Some("k"));
+    }
+
+    #[test]
+    fn parses_batch_subcommand() {
+        let args = Args::parse_from([
+            "trein", "-s", "ja", "batch", "shots", "*.png", "-j", "2", "--format", "csv", "-o",
+            "out.csv", "--resume",
+        ]);
+        assert_eq!(args.source_lang, "ja");
+        match args.command {
+            Some(Command::Batch(b)) => {
+                assert_eq!(b.inputs, ["shots", "*.png"]);
+                assert_eq!(b.jobs, Some(2));
+                assert_eq!(b.format, BatchFormat::Csv);
+                assert_eq!(b.output, Some(PathBuf::from("out.csv")));
+                assert!(b.resume);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+        assert!(Args::try_parse_from(["trein", "batch"]).is_err());
+    }
 }
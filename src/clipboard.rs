@@ -1,17 +1,26 @@
-use std::io::Write;
-use std::process::{Command, Stdio};
+use std::process::Stdio;
 
-pub fn maybe_copy_to_clipboard(copy: bool, text: &str) {
+use crate::timeouts::Stage;
+use crate::tools;
+
+pub async fn maybe_copy_to_clipboard(copy: bool, text: &str) {
     if !copy {
         return;
     }
-    if let Ok(mut child) = Command::new("wl-copy").stdin(Stdio::piped()).spawn() {
-        if let Some(mut stdin) = child.stdin.take() {
-            let _ = stdin.write_all(text.as_bytes());
+    // wl-copy keeps serving the selection in the background; don't wait on
+    // its output pipes.
+    let mut command = tools::command("wl-copy");
+    command.stdout(Stdio::null()).stderr(Stdio::null());
+    match tools::run(command, "wl-copy", Some(text.as_bytes()), Stage::Clipboard).await {
+        Ok(_) => {}
+        Err(e)
+            if e.root_cause()
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|io| io.kind() == std::io::ErrorKind::NotFound) =>
+        {
+            eprintln!("(Tip) wl-copy not found, skipping clipboard copy.")
         }
-        let _ = child.wait();
-    } else {
-        eprintln!("(Tip) wl-copy not found, skipping clipboard copy.");
+        Err(e) => eprintln!("(Warning) clipboard copy failed: {e:#}"),
     }
 }
 
@@ -20,20 +29,24 @@ mod tests {
     use super::*;
     use serial_test::serial;
 
-    #[test]
-    fn no_op_when_copy_false() {
-        maybe_copy_to_clipboard(false, "anything");
+    #[tokio::test]
+    async fn no_op_when_copy_false() {
+        maybe_copy_to_clipboard(false, "anything").await;
     }
 
-    #[test]
+    #[tokio::test]
     #[serial]
-    fn best_effort_when_wl_copy_missing() {
+    async fn best_effort_when_wl_copy_missing() {
         // Ensure wl-copy isn't found
+        let path = std::env::var_os("PATH");
         unsafe {
             std::env::set_var("PATH", "");
         }
         // Should not panic; will eprintln! a tip.
-        maybe_copy_to_clipboard(true, "hello");
+        maybe_copy_to_clipboard(true, "hello").await;
         // no assertions; just verifying it doesn't crash
+        if let Some(path) = path {
+            unsafe { std::env::set_var("PATH", path) };
+        }
     }
 }
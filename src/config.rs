@@ -1,23 +1,53 @@
-use anyhow::{bail, Result};
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::{env, fs};
 
+use crate::backend::BackendConfig;
+use crate::capture::CaptureConfig;
 use crate::cli::Args;
+use crate::http::RetryConfig;
+use crate::protect::ProtectConfig;
+use crate::timeouts::Timeouts;
+use crate::tools::ToolConfig;
+use crate::translate::DeeplOptions;
 
-pub fn resolve_deepl_api_key(args: &Args) -> Result<String> {
-    // 1) CLI flag (also populated by env if clap `env` feature is enabled)
-    if let Some(k) = args.deepl_api_key.as_deref().filter(|s| !s.is_empty()) {
-        return Ok(k.to_string());
-    }
+/// Contents of `config.toml`. The legacy single-line `DEEPL_API_KEY=...`
+/// (or bare key) format is still accepted and maps to `deepl_api_key`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub deepl_api_key: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+    pub protect: ProtectConfig,
+    /// Extra translation backends, e.g. for --compare.
+    pub backends: BTreeMap<String, BackendConfig>,
+    /// Backend names tried in order until one succeeds (default: just "deepl").
+    pub chain: Vec<String>,
+    /// More DeepL keys, tried after the main one (as "deepl[2]", "deepl[3]", …).
+    pub deepl_api_keys: Vec<String>,
+    /// Screenshot tools, when the session's defaults don't fit.
+    pub capture: CaptureConfig,
+    /// Paths and extra arguments for external tools, by name.
+    pub tools: BTreeMap<String, ToolConfig>,
+    /// Per-stage time limits.
+    pub timeouts: Timeouts,
+    /// How transient HTTP failures are retried.
+    pub retry: RetryConfig,
+}
 
-    // 2) Env var (explicit fallback)
-    if let Ok(k) = env::var("DEEPL_API_KEY") {
-        if !k.trim().is_empty() {
-            return Ok(k);
-        }
-    }
+/// A named set of defaults selected with `--profile`; `default` applies when none is given.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    #[serde(flatten)]
+    pub deepl: DeeplOptions,
+    /// DeepL glossary name or ID, as with `--glossary`.
+    pub glossary: Option<String>,
+}
 
-    // 3) Config files
+fn config_candidates() -> Vec<PathBuf> {
     let mut candidates: Vec<PathBuf> = Vec::new();
     if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
         candidates.push(PathBuf::from(xdg).join("trein/config.toml"));
@@ -25,40 +55,175 @@ pub fn resolve_deepl_api_key(args: &Args) -> Result<String> {
     if let Ok(home) = env::var("HOME") {
         candidates.push(PathBuf::from(home).join(".config/trein/config.toml"));
     }
+    candidates
+}
 
-    for p in candidates {
-        if let Ok(content) = fs::read_to_string(&p) {
-            let line = content.trim();
-            if line.is_empty() {
-                continue;
-            }
-            // Accept either "DEEPL_API_KEY=..." or just the raw value.
-            let value = if let Some(rest) = line.strip_prefix("DEEPL_API_KEY=") {
-                rest.trim()
-            } else {
-                line
-            };
-            if !value.is_empty() {
-                return Ok(value.to_string());
-            }
+/// Loads the first config file with any content; empty or missing files
+/// are skipped, and with none left the config is empty.
+pub fn load_config() -> Result<Config> {
+    for p in config_candidates() {
+        let Ok(content) = fs::read_to_string(&p) else {
+            continue;
+        };
+        if content.trim().is_empty() {
+            continue;
         }
+        return parse_config(&content).with_context(|| format!("Invalid config {}", p.display()));
+    }
+    Ok(Config::default())
+}
+
+fn parse_config(content: &str) -> Result<Config> {
+    let line = content.trim();
+    if line.is_empty() {
+        return Ok(Config::default());
+    }
+    // Legacy format: either "DEEPL_API_KEY=..." or just the raw value.
+    if !line.contains('\n') {
+        if let Some(rest) = line.strip_prefix("DEEPL_API_KEY=") {
+            return Ok(legacy_config(rest.trim()));
+        }
+        if !line.contains('=') && !line.starts_with('[') {
+            return Ok(legacy_config(line));
+        }
+    }
+    Ok(toml::from_str(content)?)
+}
+
+fn legacy_config(key: &str) -> Config {
+    Config {
+        deepl_api_key: Some(key.to_string()).filter(|k| !k.is_empty()),
+        ..Config::default()
+    }
+}
+
+pub fn resolve_deepl_api_key(args: &Args, config: &Config) -> Result<String> {
+    // 1) CLI flag (also populated by env if clap `env` feature is enabled)
+    if let Some(k) = args.deepl_api_key.as_deref().filter(|s| !s.is_empty()) {
+        return Ok(k.to_string());
+    }
+
+    // 2) Env var (explicit fallback)
+    if let Ok(k) = env::var("DEEPL_API_KEY")
+        && !k.trim().is_empty()
+    {
+        return Ok(k);
+    }
+
+    // 3) Config file
+    if let Some(k) = config
+        .deepl_api_key
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+    {
+        return Ok(k.trim().to_string());
     }
 
     bail!(
         "Set your DeepL key via --deepl-api-key, $DEEPL_API_KEY, or a config file at \
-         $XDG_CONFIG_HOME/trein/config.toml (or $HOME/.config/trein/config.toml) with a single line: \
-         DEEPL_API_KEY=..."
+         $XDG_CONFIG_HOME/trein/config.toml (or $HOME/.config/trein/config.toml) containing \
+         deepl_api_key = \"...\" (or a single line: DEEPL_API_KEY=...)"
     );
 }
 
-pub fn deepl_base_url() -> String {
-    std::env::var("DEEPL_API_BASE").unwrap_or_else(|_| "https://api-free.deepl.com".to_string())
+/// The profile named by `--profile`, else `default` if defined, else an empty one.
+pub fn selected_profile(args: &Args, config: &Config) -> Result<Profile> {
+    match args.profile.as_deref() {
+        Some(name) => config.profiles.get(name).cloned().ok_or_else(|| {
+            anyhow!("Unknown profile ‘{name}’ (define it under [profiles.{name}] in config.toml)")
+        }),
+        None => Ok(config.profiles.get("default").cloned().unwrap_or_default()),
+    }
+}
+
+/// DeepL options from the profile, overridden by CLI flags.
+pub fn resolve_deepl_options(args: &Args, profile: &Profile) -> DeeplOptions {
+    let cli = DeeplOptions {
+        formality: args.formality,
+        context: args.context.clone(),
+        split_sentences: args.split_sentences,
+        preserve_formatting: args.preserve_formatting,
+        model_type: args.model_type,
+        glossary_id: None,
+    };
+    profile.deepl.clone().merged(cli)
+}
+
+/// Which DeepL API host requests go to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeeplEndpoint {
+    Free,
+    Pro,
+    Custom(String),
+}
+
+impl DeeplEndpoint {
+    /// Parses `free`, `pro`, or an explicit base URL.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
+            "free" => Ok(Self::Free),
+            "pro" => Ok(Self::Pro),
+            _ if s.starts_with("http://") || s.starts_with("https://") => {
+                Ok(Self::Custom(s.trim_end_matches('/').to_string()))
+            }
+            _ => bail!("Invalid DeepL endpoint ‘{s}’: expected free, pro, or an http(s) URL"),
+        }
+    }
+
+    /// DeepL Free keys end in `:fx`; everything else is a Pro key.
+    pub fn for_key(api_key: &str) -> Self {
+        if api_key.trim().ends_with(":fx") {
+            Self::Free
+        } else {
+            Self::Pro
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        match self {
+            Self::Free => "https://api-free.deepl.com",
+            Self::Pro => "https://api.deepl.com",
+            Self::Custom(url) => url,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Free => "free",
+            Self::Pro => "pro",
+            Self::Custom(_) => "custom",
+        }
+    }
+}
+
+pub fn resolve_deepl_endpoint(args: &Args, api_key: &str) -> Result<DeeplEndpoint> {
+    // 1) CLI flag
+    if let Some(e) = args
+        .deepl_endpoint
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+    {
+        return DeeplEndpoint::parse(e);
+    }
+
+    // 2) Env var (kept for backwards compatibility)
+    if let Ok(e) = env::var("DEEPL_API_BASE")
+        && !e.trim().is_empty()
+    {
+        return DeeplEndpoint::parse(&e);
+    }
+
+    // 3) Derived from the key itself
+    Ok(DeeplEndpoint::for_key(api_key))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*; // brings resolve_deepl_api_key, deepl_base_url into scope
+    use super::*; // brings the resolve_* functions and Config into scope
     use crate::cli::Args; // your real CLI struct
+    use crate::translate::{Formality, ModelType, SplitSentences};
+    use clap::Parser;
     use serial_test::serial;
     use std::{env, fs};
     use tempfile::TempDir;
@@ -66,13 +231,9 @@ mod tests {
     // ----------------- helpers -----------------
 
     fn make_args(key: Option<&str>) -> Args {
-        Args {
-            source_lang: "EN".to_string(),
-            target_lang: "EN".to_string(),
-            copy: false,
-            ocr_lang: None,
-            deepl_api_key: key.map(|s| s.to_string()),
-        }
+        let mut args = Args::parse_from(["trein"]);
+        args.deepl_api_key = key.map(|s| s.to_string());
+        args
     }
 
     /// Save selected env vars, run `f`, then restore them.
@@ -102,6 +263,10 @@ mod tests {
         fs::write(p, contents).unwrap();
     }
 
+    fn resolve_key(args: &Args) -> Result<String> {
+        resolve_deepl_api_key(args, &load_config()?)
+    }
+
     // ----------------- tests -------------------
 
     #[test]
@@ -118,7 +283,7 @@ mod tests {
             unsafe { env::set_var("HOME", home.path()) };
             write_home_config(home.path(), "HOME_FILE_VAL");
 
-            let got = resolve_deepl_api_key(&make_args(Some("CLI_VAL"))).unwrap();
+            let got = resolve_key(&make_args(Some("CLI_VAL"))).unwrap();
             assert_eq!(got, "CLI_VAL");
         });
     }
@@ -134,11 +299,11 @@ mod tests {
             }
 
             // Empty CLI should be ignored
-            let got = resolve_deepl_api_key(&make_args(Some(""))).unwrap();
+            let got = resolve_key(&make_args(Some(""))).unwrap();
             assert_eq!(got, "ENV_KEY");
 
             // Or when CLI is absent
-            let got2 = resolve_deepl_api_key(&make_args(None)).unwrap();
+            let got2 = resolve_key(&make_args(None)).unwrap();
             assert_eq!(got2, "ENV_KEY");
         });
     }
@@ -157,7 +322,7 @@ mod tests {
             // Raw value (no DEEPL_API_KEY= prefix)
             write_xdg_config(xdg.path(), "FILE_KEY");
 
-            let got = resolve_deepl_api_key(&make_args(None)).unwrap();
+            let got = resolve_key(&make_args(None)).unwrap();
             assert_eq!(got, "FILE_KEY");
         });
     }
@@ -175,11 +340,30 @@ mod tests {
             unsafe { env::set_var("HOME", home.path()) };
             write_home_config(home.path(), "DEEPL_API_KEY=ABC123\n");
 
-            let got = resolve_deepl_api_key(&make_args(None)).unwrap();
+            let got = resolve_key(&make_args(None)).unwrap();
             assert_eq!(got, "ABC123");
         });
     }
 
+    #[test]
+    #[serial]
+    fn empty_xdg_config_does_not_hide_home_config() {
+        with_env_guard(&["DEEPL_API_KEY", "XDG_CONFIG_HOME", "HOME"], || {
+            unsafe { env::remove_var("DEEPL_API_KEY") };
+
+            let xdg = TempDir::new().unwrap();
+            unsafe { env::set_var("XDG_CONFIG_HOME", xdg.path()) };
+            write_xdg_config(xdg.path(), " \n");
+
+            let home = TempDir::new().unwrap();
+            unsafe { env::set_var("HOME", home.path()) };
+            write_home_config(home.path(), "deepl_api_key = \"HOME_KEY\"\n");
+
+            let got = resolve_key(&make_args(None)).unwrap();
+            assert_eq!(got, "HOME_KEY");
+        });
+    }
+
     #[test]
     #[serial]
     fn errors_when_no_sources_available() {
@@ -190,7 +374,7 @@ mod tests {
                 env::remove_var("HOME");
             }
 
-            let err = resolve_deepl_api_key(&make_args(None)).unwrap_err();
+            let err = resolve_key(&make_args(None)).unwrap_err();
             let msg = err.to_string();
             assert!(
                 msg.contains("Set your DeepL key via --deepl-api-key")
@@ -203,13 +387,104 @@ mod tests {
 
     #[test]
     #[serial]
-    fn deepl_base_url_default_and_override() {
+    fn deepl_endpoint_follows_key_suffix() {
         with_env_guard(&["DEEPL_API_BASE"], || {
             unsafe { env::remove_var("DEEPL_API_BASE") };
-            assert_eq!(deepl_base_url(), "https://api-free.deepl.com");
+            let args = make_args(None);
+
+            let free = resolve_deepl_endpoint(&args, "abc:fx").unwrap();
+            assert_eq!(free, DeeplEndpoint::Free);
+            assert_eq!(free.url(), "https://api-free.deepl.com");
 
-            unsafe { env::set_var("DEEPL_API_BASE", "https://example.invalid") };
-            assert_eq!(deepl_base_url(), "https://example.invalid");
+            let pro = resolve_deepl_endpoint(&args, "abc").unwrap();
+            assert_eq!(pro, DeeplEndpoint::Pro);
+            assert_eq!(pro.url(), "https://api.deepl.com");
         });
     }
+
+    #[test]
+    #[serial]
+    fn deepl_endpoint_cli_then_env_override_key() {
+        with_env_guard(&["DEEPL_API_BASE"], || {
+            unsafe { env::set_var("DEEPL_API_BASE", "https://example.invalid/") };
+            let mut args = make_args(None);
+            assert_eq!(
+                resolve_deepl_endpoint(&args, "abc:fx").unwrap().url(),
+                "https://example.invalid"
+            );
+
+            args.deepl_endpoint = Some("pro".into());
+            assert_eq!(
+                resolve_deepl_endpoint(&args, "abc:fx").unwrap(),
+                DeeplEndpoint::Pro
+            );
+
+            args.deepl_endpoint = Some("staging".into());
+            assert!(resolve_deepl_endpoint(&args, "abc").is_err());
+        });
+    }
+
+    #[test]
+    fn toml_config_with_profiles_is_parsed() {
+        let cfg = parse_config(
+            r#"
+deepl_api_key = "TOML_KEY"
+
+[profiles.formal]
+formality = "more"
+split_sentences = "nonewlines"
+preserve_formatting = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.deepl_api_key.as_deref(), Some("TOML_KEY"));
+        let formal = &cfg.profiles["formal"].deepl;
+        assert_eq!(formal.formality, Some(Formality::More));
+        assert_eq!(formal.split_sentences, Some(SplitSentences::Nonewlines));
+        assert_eq!(formal.preserve_formatting, Some(true));
+    }
+
+    #[test]
+    fn fallback_chain_and_extra_keys_are_parsed() {
+        let cfg = parse_config(
+            r#"
+deepl_api_key = "main:fx"
+deepl_api_keys = ["second:fx", "third"]
+chain = ["deepl", "deepl-pro"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.deepl_api_keys, ["second:fx", "third"]);
+        assert_eq!(cfg.chain, ["deepl", "deepl-pro"]);
+    }
+
+    #[test]
+    fn cli_options_override_selected_profile() {
+        let cfg = parse_config(
+            r#"
+[profiles.default]
+model_type = "latency_optimized"
+
+[profiles.formal]
+formality = "more"
+context = "customer support"
+"#,
+        )
+        .unwrap();
+
+        let mut args = make_args(None);
+        let opts = resolve_deepl_options(&args, &selected_profile(&args, &cfg).unwrap());
+        assert_eq!(opts.model_type, Some(ModelType::LatencyOptimized));
+        assert_eq!(opts.formality, None);
+
+        args.profile = Some("formal".into());
+        args.formality = Some(Formality::PreferLess);
+        let opts = resolve_deepl_options(&args, &selected_profile(&args, &cfg).unwrap());
+        assert_eq!(opts.formality, Some(Formality::PreferLess));
+        assert_eq!(opts.context.as_deref(), Some("customer support"));
+        assert_eq!(opts.model_type, None);
+
+        args.profile = Some("missing".into());
+        assert!(selected_profile(&args, &cfg).is_err());
+    }
 }
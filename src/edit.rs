@@ -0,0 +1,147 @@
+use anyhow::{Context, Result, bail};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+/// Lets the user fix the OCR text, in $VISUAL/$EDITOR when set, otherwise
+/// with a small line editor on the terminal.
+pub async fn edit_text(text: &str) -> Result<String> {
+    let edited = match editor() {
+        Some(editor) => edit_in_editor(&editor, text).await?,
+        None => edit_inline(text).await?,
+    };
+    let edited = edited.trim().to_string();
+    if edited.is_empty() {
+        bail!("The edited text is empty; nothing to translate.");
+    }
+    Ok(edited)
+}
+
+/// Shows `text` and asks before any quota is spent on it.
+pub async fn confirm(text: &str) -> Result<bool> {
+    require_terminal("--confirm")?;
+    eprintln!("OCR text:\n  {text}");
+    eprint!("Translate it? [y/N] ");
+    let _ = std::io::stderr().flush();
+    let answer = read_line().await?.unwrap_or_default();
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+/// Set while $VISUAL/$EDITOR has the terminal; Ctrl-C is then the editor's.
+static EDITOR_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl-C went to the editor rather than to trein.
+pub fn editor_running() -> bool {
+    EDITOR_RUNNING.load(Ordering::SeqCst)
+}
+
+fn editor() -> Option<Vec<String>> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|k| std::env::var(k).ok())
+        .map(|e| e.split_whitespace().map(String::from).collect::<Vec<_>>())
+        .find(|argv| !argv.is_empty())
+}
+
+async fn edit_in_editor(editor: &[String], text: &str) -> Result<String> {
+    let file = tempfile::Builder::new()
+        .prefix("trein-")
+        .suffix(".txt")
+        .tempfile()
+        .context("Could not create a file to edit")?;
+    std::fs::write(file.path(), format!("{text}\n"))?;
+    // The editor gets the terminal's Ctrl-C too; its exit status decides.
+    EDITOR_RUNNING.store(true, Ordering::SeqCst);
+    let status = Command::new(&editor[0])
+        .args(&editor[1..])
+        .arg(file.path())
+        .status()
+        .await;
+    EDITOR_RUNNING.store(false, Ordering::SeqCst);
+    let status = status.with_context(|| format!("Failed to run the editor `{}`", editor[0]))?;
+    if !status.success() {
+        bail!("`{}` exited with {status}; not translating", editor[0]);
+    }
+    std::fs::read_to_string(file.path()).context("Could not read the edited text")
+}
+
+async fn edit_inline(text: &str) -> Result<String> {
+    require_terminal("--edit without $EDITOR")?;
+    eprintln!("OCR text:\n  {text}");
+    eprintln!("Type a replacement, s/old/new/ to fix part of it, or press Enter to accept.");
+    let mut text = text.to_string();
+    loop {
+        eprint!("> ");
+        let _ = std::io::stderr().flush();
+        let Some(line) = read_line().await? else {
+            return Ok(text);
+        };
+        match apply_edit(&text, &line) {
+            Some(changed) => {
+                text = changed;
+                eprintln!("  {text}");
+            }
+            None => return Ok(text),
+        }
+    }
+}
+
+/// One line typed into the inline editor: empty accepts (`None`),
+/// `s/old/new/` replaces every `old`, anything else replaces the text.
+fn apply_edit(text: &str, line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    if let Some(rest) = line.strip_prefix("s/")
+        && let Some((from, to)) = rest.strip_suffix('/').unwrap_or(rest).split_once('/')
+        && !from.is_empty()
+    {
+        return Some(text.replace(from, to));
+    }
+    Some(line.to_string())
+}
+
+async fn read_line() -> Result<Option<String>> {
+    Ok(BufReader::new(tokio::io::stdin())
+        .lines()
+        .next_line()
+        .await?)
+}
+
+fn require_terminal(what: &str) -> Result<()> {
+    if !std::io::stdin().is_terminal() {
+        bail!("{what} needs a terminal");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_edits() {
+        let text = "Tbe rnodern world";
+        assert_eq!(apply_edit(text, ""), None);
+        assert_eq!(apply_edit(text, "  "), None);
+        assert_eq!(
+            apply_edit(text, "s/rnod/mod/").as_deref(),
+            Some("Tbe modern world")
+        );
+        assert_eq!(
+            apply_edit(text, "s/Tb/Th").as_deref(),
+            Some("The rnodern world")
+        );
+        assert_eq!(
+            apply_edit(text, "The modern world").as_deref(),
+            Some("The modern world")
+        );
+        // Not a substitution: an empty pattern replaces the whole text.
+        assert_eq!(apply_edit(text, "s//x/").as_deref(), Some("s//x/"));
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::{Context, Result, anyhow, bail};
+use reqwest::header::{ACCEPT, AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::cli::GlossaryCommand;
+use crate::http::send_with_retry;
+use crate::translate::{DeeplApi, deepl_error, deepl_source, deepl_source_for_target};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Glossary {
+    pub glossary_id: String,
+    pub name: String,
+    pub source_lang: String,
+    pub target_lang: String,
+    #[serde(default)]
+    pub entry_count: u64,
+    #[serde(default)]
+    pub ready: bool,
+}
+
+#[derive(Deserialize)]
+struct GlossaryList {
+    glossaries: Vec<Glossary>,
+}
+
+#[derive(Serialize)]
+struct CreateRequest<'a> {
+    name: &'a str,
+    source_lang: &'a str,
+    target_lang: &'a str,
+    entries: &'a str,
+    entries_format: &'a str,
+}
+
+pub async fn create_glossary(
+    api: &DeeplApi,
+    name: &str,
+    source: &str,
+    target: &str,
+    entries: &str,
+    entries_format: &str,
+) -> Result<Glossary> {
+    let url = format!("{}/v2/glossaries", api.base_url);
+    let body = CreateRequest {
+        name,
+        source_lang: source,
+        target_lang: target,
+        entries,
+        entries_format,
+    };
+    let auth = api.auth();
+    let resp = send_with_retry(&api.retry, || {
+        api.client
+            .post(&url)
+            .header(AUTHORIZATION, &auth)
+            .json(&body)
+    })
+    .await
+    .map_err(|e| deepl_error(e, &api.api_key))?;
+    resp.json()
+        .await
+        .context("Invalid glossary JSON from DeepL")
+}
+
+pub async fn list_glossaries(api: &DeeplApi) -> Result<Vec<Glossary>> {
+    let url = format!("{}/v2/glossaries", api.base_url);
+    let auth = api.auth();
+    let resp = send_with_retry(&api.retry, || {
+        api.client.get(&url).header(AUTHORIZATION, &auth)
+    })
+    .await
+    .map_err(|e| deepl_error(e, &api.api_key))?;
+    let list: GlossaryList = resp
+        .json()
+        .await
+        .context("Invalid glossary list JSON from DeepL")?;
+    Ok(list.glossaries)
+}
+
+/// Entries of a glossary as TSV.
+pub async fn glossary_entries(api: &DeeplApi, id: &str) -> Result<String> {
+    let url = format!("{}/v2/glossaries/{id}/entries", api.base_url);
+    let auth = api.auth();
+    let resp = send_with_retry(&api.retry, || {
+        api.client
+            .get(&url)
+            .header(AUTHORIZATION, &auth)
+            .header(ACCEPT, "text/tab-separated-values")
+    })
+    .await
+    .map_err(|e| deepl_error(e, &api.api_key))?;
+    Ok(resp.text().await?)
+}
+
+pub async fn delete_glossary(api: &DeeplApi, id: &str) -> Result<()> {
+    let url = format!("{}/v2/glossaries/{id}", api.base_url);
+    let auth = api.auth();
+    send_with_retry(&api.retry, || {
+        api.client.delete(&url).header(AUTHORIZATION, &auth)
+    })
+    .await
+    .map_err(|e| deepl_error(e, &api.api_key))?;
+    Ok(())
+}
+
+/// Finds a glossary by ID or by (unique) name.
+pub async fn find_glossary(api: &DeeplApi, name_or_id: &str) -> Result<Glossary> {
+    let all = list_glossaries(api).await?;
+    if let Some(g) = all.iter().find(|g| g.glossary_id == name_or_id) {
+        return Ok(g.clone());
+    }
+    let mut named = all.into_iter().filter(|g| g.name == name_or_id);
+    match (named.next(), named.next()) {
+        (Some(g), None) => Ok(g),
+        (Some(_), Some(_)) => bail!(
+            "Several DeepL glossaries are named ‘{name_or_id}’; pass the glossary ID instead \
+             (see `trein glossary list`)."
+        ),
+        (None, _) => Err(anyhow!("No DeepL glossary named ‘{name_or_id}’")),
+    }
+}
+
+/// DeepL glossaries are per base-language pair, so EN-GB matches an `en` glossary.
+pub fn check_glossary_pair(glossary: &Glossary, source: &str, target: &str) -> Result<()> {
+    let target_base = deepl_source_for_target(target)?;
+    if !glossary.source_lang.eq_ignore_ascii_case(source)
+        || !glossary.target_lang.eq_ignore_ascii_case(&target_base)
+    {
+        bail!(
+            "Glossary ‘{}’ is {}→{}, but this translation is {}→{}",
+            glossary.name,
+            glossary.source_lang.to_uppercase(),
+            glossary.target_lang.to_uppercase(),
+            source,
+            target
+        );
+    }
+    Ok(())
+}
+
+fn entries_format(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => "csv",
+        _ => "tsv",
+    }
+}
+
+fn describe(g: &Glossary) -> String {
+    format!(
+        "{}\t{}→{}\t{} entries{}\t{}",
+        g.name,
+        g.source_lang.to_uppercase(),
+        g.target_lang.to_uppercase(),
+        g.entry_count,
+        if g.ready { "" } else { " (not ready)" },
+        g.glossary_id
+    )
+}
+
+pub async fn run_glossary(api: &DeeplApi, cmd: &GlossaryCommand) -> Result<()> {
+    match cmd {
+        GlossaryCommand::Create {
+            name,
+            source_lang,
+            target_lang,
+            file,
+        } => {
+            let src = deepl_source(source_lang)?;
+            let tgt = deepl_source_for_target(target_lang)?;
+            let entries = std::fs::read_to_string(file)
+                .with_context(|| format!("Could not read {}", file.display()))?;
+            let g = create_glossary(api, name, &src, &tgt, &entries, entries_format(file)).await?;
+            println!("{}", describe(&g));
+        }
+        GlossaryCommand::List => {
+            for g in list_glossaries(api).await? {
+                println!("{}", describe(&g));
+            }
+        }
+        GlossaryCommand::Show { name } => {
+            let g = find_glossary(api, name).await?;
+            println!("{}\n", describe(&g));
+            print!("{}", glossary_entries(api, &g.glossary_id).await?);
+        }
+        GlossaryCommand::Delete { name } => {
+            let g = find_glossary(api, name).await?;
+            delete_glossary(api, &g.glossary_id).await?;
+            println!("Deleted {}", describe(&g));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::RetryPolicy;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    fn test_api(server: &MockServer) -> DeeplApi {
+        DeeplApi {
+            client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
+            api_key: "k".into(),
+            base_url: server.base_url(),
+        }
+    }
+
+    fn glossary(name: &str, src: &str, tgt: &str) -> Glossary {
+        Glossary {
+            glossary_id: format!("id-{name}"),
+            name: name.into(),
+            source_lang: src.into(),
+            target_lang: tgt.into(),
+            entry_count: 1,
+            ready: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn create_uploads_entries() {
+        let server = MockServer::start();
+        let m = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/glossaries")
+                .header("Authorization", "DeepL-Auth-Key k")
+                .json_body(json!({
+                    "name": "product",
+                    "source_lang": "EN",
+                    "target_lang": "DE",
+                    "entries": "trein\ttrein\n",
+                    "entries_format": "tsv"
+                }));
+            then.status(201).json_body(json!({
+                "glossary_id": "abc",
+                "name": "product",
+                "source_lang": "en",
+                "target_lang": "de",
+                "entry_count": 1,
+                "ready": true
+            }));
+        });
+
+        let g = create_glossary(
+            &test_api(&server),
+            "product",
+            "EN",
+            "DE",
+            "trein\ttrein\n",
+            "tsv",
+        )
+        .await
+        .unwrap();
+        assert_eq!(g.glossary_id, "abc");
+        m.assert();
+    }
+
+    #[tokio::test]
+    async fn find_by_name_or_id() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/v2/glossaries");
+            then.status(200).json_body(json!({"glossaries": [
+                {"glossary_id": "g1", "name": "ui", "source_lang": "ja", "target_lang": "de"},
+                {"glossary_id": "g2", "name": "dup", "source_lang": "ja", "target_lang": "de"},
+                {"glossary_id": "g3", "name": "dup", "source_lang": "ja", "target_lang": "pl"}
+            ]}));
+        });
+
+        let api = test_api(&server);
+        assert_eq!(find_glossary(&api, "ui").await.unwrap().glossary_id, "g1");
+        assert_eq!(find_glossary(&api, "g3").await.unwrap().name, "dup");
+        assert!(find_glossary(&api, "dup").await.is_err());
+        assert!(find_glossary(&api, "nope").await.is_err());
+    }
+
+    #[test]
+    fn pair_check_uses_base_target() {
+        let g = glossary("ui", "en", "pt");
+        assert!(check_glossary_pair(&g, "EN", "PT-BR").is_ok());
+        assert!(check_glossary_pair(&g, "EN", "DE").is_err());
+        assert!(check_glossary_pair(&g, "JA", "PT").is_err());
+    }
+}
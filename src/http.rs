@@ -0,0 +1,327 @@
+use anyhow::Result;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::timeouts::{Stage, TimedOut};
+
+/// How transient HTTP failures (429, 5xx, connection errors) are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Upper bound for all attempts and waits combined.
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+            deadline: Duration::from_secs(30),
+        }
+    }
+}
+
+/// `[retry]` in config.toml; --retries and --retry-deadline override it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub retries: u32,
+    /// Seconds for all attempts and waits of one request together.
+    pub deadline: u64,
+    /// First backoff delay, doubled after each attempt up to `max_delay_ms`.
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+        Self {
+            retries: policy.max_retries,
+            deadline: policy.deadline.as_secs(),
+            base_delay_ms: policy.base_delay.as_millis() as u64,
+            max_delay_ms: policy.max_delay.as_millis() as u64,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// The policy with the command line's `retries` and `deadline`, if given.
+    pub fn policy(&self, retries: Option<u32>, deadline: Option<u64>) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: retries.unwrap_or(self.retries),
+            base_delay: Duration::from_millis(self.base_delay_ms),
+            max_delay: Duration::from_millis(self.max_delay_ms),
+            deadline: Duration::from_secs(deadline.unwrap_or(self.deadline)),
+        }
+    }
+}
+
+/// A non-success HTTP status, kept as a typed error so callers can inspect it.
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.body.trim().is_empty() {
+            write!(f, "HTTP {}", self.status.as_u16())
+        } else {
+            write!(f, "HTTP {}: {}", self.status.as_u16(), self.body.trim())
+        }
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// Client for the translation backends; each request (body included) is
+/// limited by `[timeouts] translate`.
+pub fn client() -> reqwest::Client {
+    let mut builder = reqwest::Client::builder().connect_timeout(Duration::from_secs(10));
+    if let Some(limit) = Stage::Translate.limit() {
+        builder = builder.timeout(limit);
+    }
+    builder.build().expect("default TLS backend is available")
+}
+
+/// Says which stage ran out of time when a request hit the client timeout.
+pub fn tag_timeout(err: anyhow::Error) -> anyhow::Error {
+    let timed_out = err.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_timeout())
+    });
+    match Stage::Translate.limit() {
+        Some(after) if timed_out => err.context(TimedOut {
+            stage: Stage::Translate,
+            after,
+        }),
+        _ => err,
+    }
+}
+
+/// Sends the request built by `build`, retrying transient failures with
+/// exponential backoff and jitter. `Retry-After` is honoured when present.
+pub async fn send_with_retry<F>(policy: &RetryPolicy, mut build: F) -> Result<Response>
+where
+    F: FnMut() -> RequestBuilder,
+{
+    let start = Instant::now();
+    let mut attempt = 0;
+    loop {
+        let remaining = policy.deadline.saturating_sub(start.elapsed());
+        let sent = tokio::time::timeout(remaining, build().send())
+            .await
+            .map_err(|e| {
+                anyhow::Error::new(e).context(format!(
+                    "Request did not complete within {:?}",
+                    policy.deadline
+                ))
+            })?;
+
+        let (err, retry_after) = match sent {
+            Ok(resp) if resp.status().is_success() => return Ok(resp),
+            Ok(resp) => {
+                let status = resp.status();
+                let retry_after = retry_after(&resp);
+                let body = resp.text().await.unwrap_or_default();
+                let err = anyhow::Error::new(StatusError { status, body });
+                if !is_retryable_status(status) {
+                    return Err(err);
+                }
+                (err, retry_after)
+            }
+            Err(e) if e.is_connect() || e.is_timeout() => (anyhow::Error::new(e), None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let delay = retry_after.unwrap_or_else(|| backoff(policy, attempt));
+        if attempt >= policy.max_retries || start.elapsed() + delay > policy.deadline {
+            return Err(err.context(format!("Giving up after {} attempt(s)", attempt + 1)));
+        }
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Replaces every occurrence of `secret` in `text`, e.g. API keys echoed in errors.
+pub fn redact(text: &str, secret: &str) -> String {
+    if secret.is_empty() {
+        return text.to_string();
+    }
+    text.replace(secret, "***")
+}
+
+/// Short, non-reversible description of a key for diagnostics.
+pub fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "***".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("***{tail}")
+}
+
+/// Whether another provider could succeed where this one failed: bad or
+/// exhausted credentials, rate limits, server errors and network trouble.
+/// Errors caused by the request itself (400, 404, …) are not.
+pub fn is_provider_failure(err: &anyhow::Error) -> bool {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<StatusError>() {
+            return matches!(e.status.as_u16(), 401 | 403 | 429 | 456)
+                || e.status.is_server_error();
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return e.is_connect() || e.is_timeout();
+        }
+        if cause.is::<tokio::time::error::Elapsed>() || cause.is::<TimedOut>() {
+            return true;
+        }
+    }
+    false
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let value = resp.headers().get(reqwest::header::RETRY_AFTER)?;
+    parse_retry_after(value.to_str().ok()?)
+}
+
+// Only the delta-seconds form is used by DeepL; HTTP dates fall back to backoff.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+fn backoff(policy: &RetryPolicy, attempt: u32) -> Duration {
+    let exp = policy
+        .base_delay
+        .saturating_mul(1u32 << attempt.min(16))
+        .min(policy.max_delay);
+    // "Equal jitter": somewhere between half and the full exponential delay.
+    let half = exp / 2;
+    half + half.mul_f64(fastrand::f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    fn fast_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            deadline: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RetryPolicy::default();
+        let first = backoff(&policy, 0);
+        assert!(first >= Duration::from_millis(250) && first <= Duration::from_millis(500));
+        let late = backoff(&policy, 30);
+        assert!(late >= policy.max_delay / 2 && late <= policy.max_delay);
+    }
+
+    #[test]
+    fn retry_config_fills_defaults_and_yields_to_flags() {
+        let cfg: RetryConfig = toml::from_str("retries = 5\nbase_delay_ms = 200").unwrap();
+        let policy = cfg.policy(None, Some(10));
+        assert_eq!(policy.max_retries, 5);
+        assert_eq!(policy.base_delay, Duration::from_millis(200));
+        assert_eq!(policy.max_delay, Duration::from_secs(8));
+        assert_eq!(policy.deadline, Duration::from_secs(10));
+        assert_eq!(cfg.policy(Some(0), None).max_retries, 0);
+        assert_eq!(cfg.policy(None, None).deadline, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn retry_after_seconds_are_parsed() {
+        assert_eq!(parse_retry_after(" 3 "), Some(Duration::from_secs(3)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+
+    #[test]
+    fn secrets_are_redacted_and_masked() {
+        assert_eq!(
+            redact("bad key abc:fx given", "abc:fx"),
+            "bad key *** given"
+        );
+        assert_eq!(redact("nothing", ""), "nothing");
+        assert_eq!(mask_key("0123456789ab:fx"), "***b:fx");
+        assert_eq!(mask_key("short"), "***");
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_exhausted() {
+        let server = MockServer::start();
+        let m = server.mock(|when, then| {
+            when.method(GET).path("/busy");
+            then.status(503);
+        });
+
+        let client = reqwest::Client::new();
+        let url = server.url("/busy");
+        let err = send_with_retry(&fast_policy(2), || client.get(&url))
+            .await
+            .unwrap_err();
+
+        m.assert_hits(3);
+        let status = err.downcast_ref::<StatusError>().unwrap().status;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn quota_errors_fail_fast() {
+        let server = MockServer::start();
+        let m = server.mock(|when, then| {
+            when.method(GET).path("/quota");
+            then.status(456).body("Quota exceeded");
+        });
+
+        let client = reqwest::Client::new();
+        let url = server.url("/quota");
+        let err = send_with_retry(&fast_policy(5), || client.get(&url))
+            .await
+            .unwrap_err();
+
+        m.assert_hits(1);
+        assert!(err.to_string().contains("Quota exceeded"));
+        assert!(is_provider_failure(&err.context("DeepL quota")));
+    }
+
+    #[tokio::test]
+    async fn bad_requests_are_not_provider_failures() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/bad");
+            then.status(400);
+        });
+
+        let client = reqwest::Client::new();
+        let url = server.url("/bad");
+        let err = send_with_retry(&fast_policy(0), || client.get(&url))
+            .await
+            .unwrap_err();
+        assert!(!is_provider_failure(&err));
+
+        // Nothing listens on port 9 (discard) on a test machine.
+        let err = send_with_retry(&fast_policy(0), || client.get("http://127.0.0.1:9/"))
+            .await
+            .unwrap_err();
+        assert!(is_provider_failure(&err));
+    }
+}
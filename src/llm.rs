@@ -0,0 +1,391 @@
+use anyhow::{Context, Result, bail};
+use reqwest::Response;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::http::{RetryPolicy, send_with_retry};
+use crate::local::is_loopback_url;
+
+const DEFAULT_SYSTEM_PROMPT: &str = "You are a professional translator. Translate the user's \
+message from {source} to {target}. The text was read from the screen by OCR (confidence: \
+{confidence}). Keep placeholders such as {{0}} exactly as they are. Reply with the translation \
+only, without quotes or explanations.";
+
+const FIX_OCR_PROMPT: &str = "The OCR may have misread some characters or words; silently fix \
+obvious recognition errors before translating.";
+
+/// `type = "openai"`: any OpenAI-compatible chat completions API, including
+/// local servers (llama.cpp, Ollama, vLLM, …).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LlmConfig {
+    /// Base URL; requests go to `{url}/chat/completions`.
+    pub url: String,
+    /// Defaults to $OPENAI_API_KEY; local servers usually need none.
+    pub api_key: Option<String>,
+    pub model: String,
+    pub temperature: Option<f32>,
+    /// Template with `{source}`, `{target}` and `{confidence}`.
+    pub system_prompt: Option<String>,
+    /// Extra instructions appended to the prompt ("keep honorifics", …).
+    pub instructions: Option<String>,
+    /// Ask the model to correct obvious OCR errors first.
+    pub fix_ocr: bool,
+    /// Stream tokens (server-sent events) when the caller wants them.
+    pub stream: bool,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            url: "https://api.openai.com/v1".into(),
+            api_key: None,
+            model: String::new(),
+            temperature: None,
+            system_prompt: None,
+            instructions: None,
+            fix_ocr: false,
+            stream: true,
+        }
+    }
+}
+
+impl LlmConfig {
+    pub fn is_local(&self) -> bool {
+        is_loopback_url(&self.url)
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct StreamChunk {
+    choices: Vec<StreamChoice>,
+}
+
+#[derive(Deserialize)]
+struct StreamChoice {
+    delta: StreamDelta,
+}
+
+#[derive(Deserialize)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum SseLine {
+    Text(String),
+    Done,
+    Other,
+}
+
+/// One line of a chat completions event stream.
+fn parse_sse_line(line: &str) -> SseLine {
+    let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+        return SseLine::Other;
+    };
+    if data == "[DONE]" {
+        return SseLine::Done;
+    }
+    let text = serde_json::from_str::<StreamChunk>(data)
+        .ok()
+        .and_then(|c| c.choices.into_iter().next())
+        .and_then(|c| c.delta.content);
+    match text {
+        Some(t) if !t.is_empty() => SseLine::Text(t),
+        _ => SseLine::Other,
+    }
+}
+
+async fn read_stream(mut resp: Response, on_token: &dyn Fn(&str)) -> Result<String> {
+    let mut buf: Vec<u8> = Vec::new();
+    let mut out = String::new();
+    while let Some(chunk) = resp.chunk().await.context("The stream was interrupted")? {
+        buf.extend_from_slice(&chunk);
+        while let Some(end) = buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=end).collect();
+            match parse_sse_line(String::from_utf8_lossy(&line).trim()) {
+                SseLine::Text(t) => {
+                    on_token(&t);
+                    out.push_str(&t);
+                }
+                SseLine::Done => return Ok(out.trim().to_string()),
+                SseLine::Other => {}
+            }
+        }
+    }
+    Ok(out.trim().to_string())
+}
+
+pub struct LlmEngine {
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    config: LlmConfig,
+    api_key: Option<String>,
+    /// Mean OCR word confidence (0–100) of the text being translated.
+    ocr_confidence: Option<f32>,
+}
+
+impl LlmEngine {
+    pub fn new(
+        config: &LlmConfig,
+        client: &reqwest::Client,
+        retry: &RetryPolicy,
+        ocr_confidence: Option<f32>,
+    ) -> Result<Self> {
+        if config.model.is_empty() {
+            bail!("An openai backend needs `model = \"…\"`");
+        }
+        let api_key = config
+            .api_key
+            .clone()
+            .or_else(|| std::env::var("OPENAI_API_KEY").ok())
+            .filter(|k| !k.trim().is_empty());
+        Ok(Self {
+            client: client.clone(),
+            retry: retry.clone(),
+            config: config.clone(),
+            api_key,
+            ocr_confidence,
+        })
+    }
+
+    pub fn system_prompt(&self, src: &str, tgt: &str) -> String {
+        let confidence = match self.ocr_confidence {
+            Some(c) => format!("{c:.0}%"),
+            None => "unknown".into(),
+        };
+        let template = self
+            .config
+            .system_prompt
+            .as_deref()
+            .unwrap_or(DEFAULT_SYSTEM_PROMPT);
+        let mut prompt = template
+            .replace("{source}", src)
+            .replace("{target}", tgt)
+            .replace("{confidence}", &confidence);
+        if self.config.fix_ocr {
+            prompt.push(' ');
+            prompt.push_str(FIX_OCR_PROMPT);
+        }
+        if let Some(extra) = &self.config.instructions {
+            prompt.push(' ');
+            prompt.push_str(extra.trim());
+        }
+        prompt
+    }
+
+    pub async fn translate(
+        &self,
+        text: &str,
+        src: &str,
+        tgt: &str,
+        on_token: Option<&dyn Fn(&str)>,
+    ) -> Result<String> {
+        let on_token = on_token.filter(|_| self.config.stream);
+        let url = format!("{}/chat/completions", self.config.url.trim_end_matches('/'));
+        let mut body = json!({
+            "model": self.config.model,
+            "messages": [
+                { "role": "system", "content": self.system_prompt(src, tgt) },
+                { "role": "user", "content": text },
+            ],
+        });
+        if let Some(t) = self.config.temperature {
+            body["temperature"] = json!(t);
+        }
+        if on_token.is_some() {
+            body["stream"] = json!(true);
+        }
+        let resp = send_with_retry(&self.retry, || {
+            let req = self.client.post(&url).json(&body);
+            match &self.api_key {
+                Some(key) => req.header(AUTHORIZATION, format!("Bearer {key}")),
+                None => req,
+            }
+        })
+        .await
+        .with_context(|| format!("Failed to contact {}", self.config.url))?;
+        // Servers that ignore `stream` answer with a plain completion.
+        let content = match on_token {
+            Some(on_token) if is_event_stream(&resp) => read_stream(resp, on_token).await?,
+            Some(on_token) => {
+                let content = read_completion(resp, &url).await?;
+                on_token(&content);
+                content
+            }
+            None => read_completion(resp, &url).await?,
+        };
+        if content.is_empty() {
+            bail!("The model returned an empty translation");
+        }
+        Ok(content)
+    }
+}
+
+fn is_event_stream(resp: &Response) -> bool {
+    resp.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"))
+}
+
+async fn read_completion(resp: Response, url: &str) -> Result<String> {
+    let resp: ChatResponse = resp
+        .json()
+        .await
+        .with_context(|| format!("Invalid JSON from {url}"))?;
+    let content = resp
+        .choices
+        .into_iter()
+        .next()
+        .map(|c| c.message.content)
+        .context("The model returned no choices")?;
+    Ok(content.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    fn engine(config: LlmConfig, confidence: Option<f32>) -> LlmEngine {
+        LlmEngine::new(
+            &config,
+            &reqwest::Client::new(),
+            &RetryPolicy::default(),
+            confidence,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn prompt_template_is_filled_in() {
+        let config = LlmConfig {
+            model: "m".into(),
+            system_prompt: Some("{source}→{target} at {confidence}, keep {{0}}.".into()),
+            instructions: Some("Keep honorifics.".into()),
+            fix_ocr: true,
+            ..Default::default()
+        };
+        let prompt = engine(config, Some(87.4)).system_prompt("JA", "EN-GB");
+        assert!(prompt.starts_with("JA→EN-GB at 87%, keep {{0}}. The OCR may"));
+        assert!(prompt.ends_with("Keep honorifics."));
+    }
+
+    #[tokio::test]
+    async fn chat_completion_is_sent_and_read() {
+        let server = MockServer::start();
+        let m = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/chat/completions")
+                .header("Authorization", "Bearer sk-test")
+                .json_body_partial(r#"{"model": "local-model", "temperature": 0.5}"#)
+                .body_contains(r#"{"content":"こんにちは","role":"user"}"#);
+            then.status(200).json_body(json!({
+                "choices": [{"message": {"role": "assistant", "content": " Hello \n"}}]
+            }));
+        });
+
+        let config = LlmConfig {
+            url: server.url("/v1/"),
+            api_key: Some("sk-test".into()),
+            model: "local-model".into(),
+            temperature: Some(0.5),
+            ..Default::default()
+        };
+        assert!(config.is_local());
+        let out = engine(config, None)
+            .translate("こんにちは", "JA", "EN-GB", None)
+            .await
+            .unwrap();
+        assert_eq!(out, "Hello");
+        m.assert();
+    }
+
+    #[tokio::test]
+    async fn streamed_tokens_are_passed_on_and_joined() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/chat/completions")
+                .json_body_partial(r#"{"stream": true}"#);
+            then.status(200)
+                .header("Content-Type", "text/event-stream")
+                .body(concat!(
+                    "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+                    "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+                    ": keep-alive\n\n",
+                    "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n",
+                    "data: [DONE]\n\n",
+                ));
+        });
+
+        let config = LlmConfig {
+            url: server.base_url(),
+            model: "m".into(),
+            ..Default::default()
+        };
+        let seen = std::cell::RefCell::new(Vec::new());
+        let on_token = |t: &str| seen.borrow_mut().push(t.to_string());
+        let out = engine(config, None)
+            .translate("Hallo", "DE", "EN-GB", Some(&on_token))
+            .await
+            .unwrap();
+        assert_eq!(out, "Hello");
+        assert_eq!(*seen.borrow(), ["Hel", "lo"]);
+    }
+
+    #[tokio::test]
+    async fn plain_answers_to_stream_requests_are_read() {
+        let server = MockServer::start();
+        let mut answer = server.mock(|when, then| {
+            when.method(POST).path("/chat/completions");
+            then.status(200).json_body(json!({
+                "choices": [{"message": {"role": "assistant", "content": "Hello"}}]
+            }));
+        });
+        let config = LlmConfig {
+            url: server.base_url(),
+            model: "m".into(),
+            ..Default::default()
+        };
+        let seen = std::cell::RefCell::new(Vec::new());
+        let on_token = |t: &str| seen.borrow_mut().push(t.to_string());
+        let llm = engine(config, None);
+        let out = llm
+            .translate("Hallo", "DE", "EN-GB", Some(&on_token))
+            .await
+            .unwrap();
+        assert_eq!(out, "Hello");
+        assert_eq!(*seen.borrow(), ["Hello"]);
+
+        answer.delete();
+        server.mock(|when, then| {
+            when.method(POST).path("/chat/completions");
+            then.status(200).json_body(json!({
+                "choices": [{"message": {"role": "assistant", "content": " "}}]
+            }));
+        });
+        let err = llm
+            .translate("Hallo", "DE", "EN-GB", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("empty translation"));
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt;
+
+use crate::http::{RetryPolicy, send_with_retry};
+use crate::timeouts::{Stage, TimedOut};
+use crate::tools;
+use crate::translate::deepl_source_for_target;
+
+/// `type = "command"`: a locally installed translator (Argos Translate,
+/// Bergamot, a CTranslate2 wrapper, …) that reads text on stdin and prints
+/// the translation on stdout.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CommandBackendConfig {
+    /// Program and arguments; `{source}` and `{target}` are replaced with
+    /// lower-case language codes ("en", "de").
+    pub command: Vec<String>,
+    /// Prints one installed pair per line ("en de", "en-de" or "en->de").
+    pub languages: Vec<String>,
+    /// Installed pairs, when there is no `languages` command.
+    pub pairs: Vec<String>,
+}
+
+/// `type = "libretranslate"`: a LibreTranslate-compatible server, e.g. a
+/// local Argos Translate install behind `libretranslate`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LibreTranslateConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+}
+
+impl LibreTranslateConfig {
+    pub fn is_local(&self) -> bool {
+        is_loopback_url(&self.url)
+    }
+}
+
+/// Only loopback servers count as offline.
+pub fn is_loopback_url(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    match url.host_str() {
+        Some("localhost") => true,
+        Some(host) => host
+            .trim_matches(|c| c == '[' || c == ']')
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback()),
+        None => false,
+    }
+}
+
+/// A local engine that could not serve the request (pair not installed,
+/// program missing or failing), so another backend may be tried.
+#[derive(Debug)]
+pub struct Unavailable(pub String);
+
+impl fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Unavailable {}
+
+/// Lower-case base language, the way local engines name them ("EN-GB" → "en").
+pub fn local_code(code: &str) -> String {
+    deepl_source_for_target(code)
+        .unwrap_or_else(|_| code.to_string())
+        .to_ascii_lowercase()
+}
+
+/// Installed (source, target) pairs; `None` when the engine can't tell.
+type Pairs = Option<Vec<(String, String)>>;
+
+fn check_pair(pairs: &Pairs, src: &str, tgt: &str) -> Result<()> {
+    let Some(pairs) = pairs else {
+        return Ok(());
+    };
+    if pairs.iter().any(|(s, t)| s == src && t == tgt) {
+        return Ok(());
+    }
+    let installed: Vec<String> = pairs.iter().map(|(s, t)| format!("{s}→{t}")).collect();
+    Err(Unavailable(format!(
+        "{src}→{tgt} is not installed locally (available: {})",
+        installed.join(", ")
+    ))
+    .into())
+}
+
+fn parse_pair(line: &str) -> Option<(String, String)> {
+    let line = line.replace("->", " ").replace(['-', '_', '\t'], " ");
+    let mut codes = line.split_whitespace().map(str::to_ascii_lowercase);
+    match (codes.next(), codes.next(), codes.next()) {
+        (Some(s), Some(t), None) => Some((s, t)),
+        _ => None,
+    }
+}
+
+fn parse_pairs(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+        .filter_map(parse_pair)
+        .collect()
+}
+
+pub struct CommandEngine {
+    argv: Vec<String>,
+    pairs: Pairs,
+}
+
+impl CommandEngine {
+    pub async fn from_config(cfg: &CommandBackendConfig) -> Result<Self> {
+        if cfg.command.is_empty() {
+            bail!("A command backend needs `command = [\"program\", …]`");
+        }
+        let pairs = if !cfg.languages.is_empty() {
+            Some(parse_pairs(&run(&cfg.languages, None).await?))
+        } else if !cfg.pairs.is_empty() {
+            Some(cfg.pairs.iter().filter_map(|p| parse_pair(p)).collect())
+        } else {
+            None
+        };
+        Ok(Self {
+            argv: cfg.command.clone(),
+            pairs,
+        })
+    }
+
+    pub async fn translate(&self, text: &str, src: &str, tgt: &str) -> Result<String> {
+        let (src, tgt) = (local_code(src), local_code(tgt));
+        check_pair(&self.pairs, &src, &tgt)?;
+        let argv: Vec<String> = self
+            .argv
+            .iter()
+            .map(|a| a.replace("{source}", &src).replace("{target}", &tgt))
+            .collect();
+        let out = run(&argv, Some(text)).await?;
+        Ok(out.trim_end().to_string())
+    }
+}
+
+/// Runs `argv`, feeding `input` on stdin, and returns stdout.
+async fn run(argv: &[String], input: Option<&str>) -> Result<String> {
+    let (program, args) = argv.split_first().context("Empty command")?;
+    let mut command = tools::command(program);
+    command.args(args);
+    let out = tools::run(command, program, input.map(str::as_bytes), Stage::Translate)
+        .await
+        .map_err(|e| match e.is::<TimedOut>() {
+            true => e,
+            false => Unavailable(format!("{e:#}")).into(),
+        })?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        return Err(Unavailable(format!("{program} failed: {}", stderr.trim())).into());
+    }
+    String::from_utf8(out.stdout).with_context(|| format!("{program} printed invalid UTF-8"))
+}
+
+#[derive(Deserialize)]
+struct LibreLanguage {
+    code: String,
+    #[serde(default)]
+    targets: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct LibreResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+}
+
+pub struct LibreTranslate {
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    url: String,
+    api_key: Option<String>,
+    pairs: Pairs,
+}
+
+impl LibreTranslate {
+    /// Asks the server for its installed languages up front.
+    pub async fn connect(
+        cfg: &LibreTranslateConfig,
+        client: &reqwest::Client,
+        retry: &RetryPolicy,
+    ) -> Result<Self> {
+        let url = cfg.url.trim_end_matches('/').to_string();
+        let languages_url = format!("{url}/languages");
+        let languages: Vec<LibreLanguage> = send_with_retry(retry, || client.get(&languages_url))
+            .await
+            .with_context(|| format!("Failed to contact {url}"))?
+            .json()
+            .await
+            .with_context(|| format!("Invalid JSON from {languages_url}"))?;
+        Ok(Self {
+            client: client.clone(),
+            retry: retry.clone(),
+            url,
+            api_key: cfg.api_key.clone(),
+            pairs: libre_pairs(&languages),
+        })
+    }
+
+    pub async fn translate(&self, text: &str, src: &str, tgt: &str) -> Result<String> {
+        let (src, tgt) = (local_code(src), local_code(tgt));
+        check_pair(&self.pairs, &src, &tgt)?;
+        let url = format!("{}/translate", self.url);
+        let mut body = json!({ "q": text, "source": src, "target": tgt, "format": "text" });
+        if let Some(key) = &self.api_key {
+            body["api_key"] = json!(key);
+        }
+        let resp: LibreResponse =
+            send_with_retry(&self.retry, || self.client.post(&url).json(&body))
+                .await
+                .with_context(|| format!("Failed to contact {}", self.url))?
+                .json()
+                .await
+                .with_context(|| format!("Invalid JSON from {url}"))?;
+        Ok(resp.translated_text)
+    }
+}
+
+// Older servers don't list targets; any installed language pairs with any other.
+fn libre_pairs(languages: &[LibreLanguage]) -> Pairs {
+    let codes: Vec<&str> = languages.iter().map(|l| l.code.as_str()).collect();
+    let pairs = languages
+        .iter()
+        .flat_map(|l| {
+            let targets = match l.targets.is_empty() {
+                true => codes.clone(),
+                false => l.targets.iter().map(String::as_str).collect(),
+            };
+            targets
+                .into_iter()
+                .filter(|t| *t != l.code)
+                .map(|t| (l.code.clone(), t.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    Some(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    #[test]
+    fn pairs_are_parsed_in_common_spellings() {
+        let pairs = parse_pairs("# installed\nen de\nEN->PL\nja_en\n\ngarbage\n");
+        assert_eq!(
+            pairs,
+            [("en", "de"), ("en", "pl"), ("ja", "en")].map(|(s, t)| (s.to_string(), t.to_string()))
+        );
+        assert!(check_pair(&Some(pairs.clone()), "en", "pl").is_ok());
+        let err = check_pair(&Some(pairs), "de", "en").unwrap_err();
+        assert!(err.is::<Unavailable>());
+    }
+
+    // Needs PATH, which the clipboard test clears for a moment.
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn command_engine_substitutes_languages_and_pipes_text() {
+        let cfg = CommandBackendConfig {
+            command: vec![
+                "sh".into(),
+                "-c".into(),
+                "printf '%s:' \"$0\" \"$1\"; tr a-z A-Z".into(),
+                "{source}".into(),
+                "{target}".into(),
+            ],
+            pairs: vec!["en-de".into()],
+            ..Default::default()
+        };
+        let engine = CommandEngine::from_config(&cfg).await.unwrap();
+        let out = engine.translate("hello\n", "EN", "DE").await.unwrap();
+        assert_eq!(out, "en:de:HELLO");
+        assert!(engine.translate("hello", "EN", "FR").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn libretranslate_checks_pairs_and_translates() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/languages");
+            then.status(200).json_body(serde_json::json!([
+                {"code": "en", "name": "English", "targets": ["de"]},
+                {"code": "de", "name": "German", "targets": ["en"]}
+            ]));
+        });
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/translate")
+                .json_body_partial(r#"{"q": "Hello", "source": "en", "target": "de"}"#);
+            then.status(200)
+                .json_body(serde_json::json!({"translatedText": "Hallo"}));
+        });
+
+        let cfg = LibreTranslateConfig {
+            url: server.base_url(),
+            api_key: None,
+        };
+        assert!(cfg.is_local());
+        let engine =
+            LibreTranslate::connect(&cfg, &reqwest::Client::new(), &RetryPolicy::default())
+                .await
+                .unwrap();
+        assert_eq!(
+            engine.translate("Hello", "EN", "DE").await.unwrap(),
+            "Hallo"
+        );
+        assert!(engine.translate("Hello", "EN", "JA").await.is_err());
+
+        let remote = LibreTranslateConfig {
+            url: "https://libretranslate.com".into(),
+            api_key: None,
+        };
+        assert!(!remote.is_local());
+    }
+}
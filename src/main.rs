@@ -1,72 +1,635 @@
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use clap::Parser;
+use futures::future::{join_all, try_join_all};
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::time::Duration;
 use tempfile::TempDir;
 
+mod artifacts;
+mod backend;
+mod backtranslate;
+mod batch;
+mod capture;
 mod cli;
 mod clipboard;
 mod config;
+mod edit;
+mod glossary;
+mod http;
+mod llm;
+mod local;
 mod ocr;
 mod output;
+mod protect;
+mod region;
+mod state;
 mod tesseract;
+mod timeouts;
+mod tools;
 mod translate;
+mod watch;
 mod wayland;
+mod x11;
 
-use crate::cli::Args;
+use crate::artifacts::Artifacts;
+use crate::backend::{Backend, Chain, DEFAULT_BACKEND, compare, needs_network};
+use crate::backtranslate::back_translate;
+use crate::batch::{BatchJob, run_batch};
+use crate::capture::Capturer;
+use crate::cli::{Args, Command, OutputFormat};
 use crate::clipboard::maybe_copy_to_clipboard;
-use crate::config::{deepl_base_url, resolve_deepl_api_key};
-use crate::ocr::{capture_region, ocr_image, select_region};
-use crate::output::print_result;
+use crate::config::{
+    Config, load_config, resolve_deepl_api_key, resolve_deepl_endpoint, resolve_deepl_options,
+    selected_profile,
+};
+use crate::edit::{confirm, edit_text};
+use crate::glossary::{check_glossary_pair, run_glossary};
+use crate::http::{RetryPolicy, mask_key, redact};
+use crate::ocr::ocr_image;
+use crate::output::{
+    Segment, StreamEcho, clipboard_text, print_comparison, print_dry_run, print_result,
+    print_segments, render_comparison_json, segments_clipboard_text,
+};
+use crate::protect::Protection;
+use crate::region::{
+    Geometry, ensure_on_screen, load_last_region, named_region, run_region, save_last_region,
+};
 use crate::tesseract::tesseract_pack_from_deepl_source;
-use crate::translate::{deepl_source, deepl_target, translate_deepl};
-use crate::wayland::require_wayland;
+use crate::translate::{DeeplApi, DeeplOptions, Translation, deepl_source, deepl_target};
+use crate::watch::{WatchOptions, run_watch};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Parse CLI and ensure we’re on Wayland
     let args = Args::parse();
-    require_wayland()?;
+    let config = load_config()?;
+    tools::init(config.tools.clone());
+    timeouts::init(config.timeouts.clone());
+
+    tokio::select! {
+        biased;
+        result = run(args, config) => result,
+        _ = interrupted() => {
+            // `run` is dropped by now: its child processes are killed and
+            // temp dirs removed.
+            eprintln!("Interrupted.");
+            std::process::exit(130)
+        }
+    }
+}
+
+/// Ctrl-C, unless the editor had the terminal: it got the key too, and vim
+/// and others use it, so their exit status decides instead.
+async fn interrupted() {
+    loop {
+        let _ = tokio::signal::ctrl_c().await;
+        if !edit::editor_running() {
+            return;
+        }
+    }
+}
+
+async fn run(args: Args, config: Config) -> Result<()> {
+    // Subcommands don't touch the screen
+    if let Some(Command::Glossary(cmd)) = &args.command {
+        if args.offline {
+            bail!("--offline: glossaries live on the DeepL servers");
+        }
+        return run_glossary(&deepl_api(&args, &config)?, cmd).await;
+    }
+
+    if let Some(Command::Region(cmd)) = &args.command {
+        return run_region(cmd, &config.capture).await;
+    }
+
+    let profile = selected_profile(&args, &config)?;
+    let deepl_options = resolve_deepl_options(&args, &profile);
+    let glossary = args.glossary.clone().or(profile.glossary);
 
     // Validate DeepL codes (strict) and decide the Tesseract pack
     let src = deepl_source(&args.source_lang)?; // e.g., "EN", "ZH"
-    let tgt = deepl_target(&args.target_lang)?; // e.g., "EN-GB", "PT-BR"
+    let targets = args
+        .target_lang
+        .iter()
+        .map(|t| deepl_target(t)) // e.g., "EN-GB", "PT-BR"
+        .collect::<Result<Vec<_>>>()?;
     let ocr_pack = if let Some(p) = &args.ocr_lang {
         p.clone()
     } else {
         tesseract_pack_from_deepl_source(&src)?.to_string() // e.g., EN→eng, ZH→chi_sim
     };
 
-    // 1) Region select → geometry
-    let geometry = select_region()?;
+    let protection = Protection::new(config.protect.clone());
+    let protect = (!args.no_protect).then_some(&protection);
+    let ocr_label = format!("{} / {}", src, ocr_pack); // show DeepL src + Tesseract pack
+    let extra_options = deepl_options.clone();
+    let default = default_backend(
+        &args,
+        &config,
+        deepl_options,
+        glossary.as_deref(),
+        &src,
+        &targets,
+    );
+
+    // Image files instead of the screen
+    if let Some(Command::Batch(cmd)) = &args.command {
+        let unsupported: Vec<&str> = [
+            ("--dry-run", args.dry_run),
+            ("--edit", args.edit),
+            ("--confirm", args.confirm),
+            ("--back-translate", args.back_translate),
+            ("--compare", args.compare.is_some()),
+            ("--keep-artifacts", args.keep_artifacts.is_some()),
+            ("--save-image", args.save_image.is_some()),
+            ("--multi", args.multi),
+            ("--watch", args.watch),
+            ("--copy", args.copy),
+        ]
+        .into_iter()
+        .filter_map(|(flag, set)| set.then_some(flag))
+        .collect();
+        if !unsupported.is_empty() {
+            bail!("`trein batch` doesn't support {}", unsupported.join(", "));
+        }
+        let job = BatchJob {
+            src: &src,
+            targets: &targets,
+            ocr_pack: &ocr_pack,
+            ocr_label: &ocr_label,
+            protect,
+        };
+        let chain = backend_chain(&args, &config, default, extra_options, None);
+        return run_batch(cmd, &job, chain).await;
+    }
+
+    // Pick the screenshot tools for this session
+    let capturer = Capturer::from_config(&config.capture)?;
+
+    // 1) Region(s): explicit, saved, the last one, or selected
+    let geometries = match (args.geometry, &args.region, args.last_region) {
+        (Some(g), _, _) => vec![g],
+        (None, Some(name), _) => vec![named_region(name)?],
+        (None, None, true) => vec![load_last_region()?],
+        (None, None, false) if args.multi => capturer.select_many(args.select).await?,
+        (None, None, false) => vec![capturer.select(args.select).await?],
+    };
+    let geometry = geometries[0];
+    if args.region.is_some() || args.last_region {
+        ensure_on_screen(&geometry, &capturer).await?;
+    }
+    if !args.multi
+        && let Err(e) = save_last_region(&geometry)
+    {
+        eprintln!("(Warning) could not remember the region: {e:#}");
+    }
+
+    // Watch mode: keep capturing and translate each new text once
+    if args.watch {
+        let chain = backend_chain(&args, &config, default, extra_options, None).await?;
+        let opts = WatchOptions {
+            interval: Duration::from_secs_f64(args.interval),
+            stable_polls: args.debounce,
+        };
+        let mut cache: HashMap<String, Vec<Translation>> = HashMap::new();
+        return run_watch(
+            &capturer,
+            &geometry,
+            &ocr_pack,
+            &opts,
+            async |text: String| {
+                let translations = match cache.get(&text) {
+                    Some(cached) => cached.clone(),
+                    None => {
+                        let fresh =
+                            translate_all(&chain, &text, &src, &targets, protect, &args).await?;
+                        cache.insert(text.clone(), fresh.clone());
+                        fresh
+                    }
+                };
+                print_result(args.format, &ocr_label, &text, &translations);
+                maybe_copy_to_clipboard(args.copy, &clipboard_text(&translations, args.copy_all))
+                    .await;
+                Ok(())
+            },
+        )
+        .await;
+    }
+
+    let artifacts = match &args.keep_artifacts {
+        Some(dir) => Some(Artifacts::create(dir)?),
+        None => None,
+    };
+
+    // Several regions: OCR each, then translate them separately or as one text
+    if args.multi {
+        let mut texts = Vec::new();
+        let mut confidence: Option<f32> = None;
+        for (i, g) in geometries.iter().enumerate() {
+            let (tmpdir, png_path) = capturer.capture(g).await?;
+            let region_artifacts = artifacts.as_ref().map(|a| a.for_region(i + 1));
+            if let Some(a) = &region_artifacts {
+                a.keep("capture.png", &png_path, true);
+            }
+            let ocr = ocr_image(&png_path, &ocr_pack, region_artifacts.as_ref()).await?;
+            drop(tmpdir);
+            if ocr.text.trim().is_empty() {
+                eprintln!("(Warning) no text in region {g}; skipping it");
+                continue;
+            }
+            if let Some(c) = ocr.confidence {
+                confidence = Some(confidence.map_or(c, |m| m.min(c)));
+            }
+            texts.push((*g, ocr.text));
+        }
+        if texts.is_empty() {
+            bail!("OCR returned no text in any region.");
+        }
+        if args.verbose
+            && let Some(c) = confidence
+        {
+            eprintln!("OCR confidence (lowest region): {c:.0}%");
+        }
+        if args.dry_run {
+            let texts: Vec<String> = match args.join {
+                true => vec![join_texts(&texts)],
+                false => texts.into_iter().map(|(_, t)| t).collect(),
+            };
+            let mut reviewed = Vec::new();
+            for text in texts {
+                reviewed.extend(review(text, &args).await?);
+            }
+            print_dry_run(args.format, &ocr_label, &reviewed, &targets);
+            return Ok(());
+        }
+        let chain = backend_chain(&args, &config, default, extra_options, confidence).await?;
+
+        if args.join {
+            let text = join_texts(&texts);
+            let Some(text) = review(text, &args).await? else {
+                eprintln!("Not translated.");
+                return Ok(());
+            };
+            let translations = translate_all(&chain, &text, &src, &targets, protect, &args).await;
+            save_translation_log(artifacts.as_ref(), &chain, &args, &config);
+            let translations = translations?;
+            print_result(args.format, &ocr_label, &text, &translations);
+            maybe_copy_to_clipboard(args.copy, &clipboard_text(&translations, args.copy_all)).await;
+            return Ok(());
+        }
+        let mut segments = Vec::new();
+        let mut failed = None;
+        for (region, text) in texts {
+            let Some(text) = review(text, &args).await? else {
+                continue;
+            };
+            match translate_all(&chain, &text, &src, &targets, protect, &args).await {
+                Ok(translations) => segments.push(Segment {
+                    region,
+                    text,
+                    translations,
+                }),
+                Err(e) => {
+                    failed = Some(e);
+                    break;
+                }
+            }
+        }
+        save_translation_log(artifacts.as_ref(), &chain, &args, &config);
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        if segments.is_empty() {
+            eprintln!("Not translated.");
+            return Ok(());
+        }
+        print_segments(args.format, &ocr_label, &segments);
+        maybe_copy_to_clipboard(
+            args.copy,
+            &segments_clipboard_text(&segments, args.copy_all),
+        )
+        .await;
+        return Ok(());
+    }
 
     // 2) Screenshot to temp file
-    let (tmpdir, png_path): (TempDir, std::path::PathBuf) = capture_region(&geometry)?;
+    let (tmpdir, png_path): (TempDir, std::path::PathBuf) = capturer.capture(&geometry).await?;
+    if let Some(path) = &args.save_image {
+        std::fs::copy(&png_path, path)
+            .with_context(|| format!("Could not save the capture to {}", path.display()))?;
+    }
+    if let Some(a) = &artifacts {
+        a.keep("capture.png", &png_path, true);
+    }
 
     // 3) OCR with the decided Tesseract pack
-    let ocr_text = ocr_image(&png_path, &ocr_pack)?;
+    let ocr = ocr_image(&png_path, &ocr_pack, artifacts.as_ref()).await?;
+    let ocr_text = ocr.text;
+    if args.verbose
+        && let Some(c) = ocr.confidence
+    {
+        eprintln!("OCR confidence: {c:.0}%");
+    }
     if ocr_text.trim().is_empty() {
         bail!("OCR returned no text. Try a larger or clearer selection, or adjust --ocr-pack.");
     }
+    let Some(ocr_text) = review(ocr_text, &args).await? else {
+        eprintln!("Not translated.");
+        return Ok(());
+    };
+    if args.dry_run {
+        print_dry_run(args.format, &ocr_label, &[ocr_text], &targets);
+        return Ok(());
+    }
 
-    // 4) Translate with DeepL (use explicit source & target)
-    let api_key = resolve_deepl_api_key(&args)?;
-    let base = deepl_base_url();
-    let client = reqwest::Client::new();
+    // 4) Translate into every target at once (explicit source & targets)
+    if let Some(names) = &args.compare {
+        let names = match names.is_empty() {
+            true => configured_backends(&config),
+            false => names.clone(),
+        };
+        let names = offline_backends(names, &config, args.offline)?;
+        let backends = build_backends(
+            &names,
+            &config,
+            default,
+            extra_options,
+            &args,
+            ocr.confidence,
+        )
+        .await;
+        let comparisons = compare(&backends, &ocr_text, &src, &targets, protect).await;
+
+        if let Some(a) = &artifacts {
+            let json = render_comparison_json(&ocr_label, &ocr_text, &comparisons);
+            a.save("comparison.json", redact_secrets(json, &args, &config));
+        }
+        print_comparison(args.format, &ocr_label, &ocr_text, &comparisons);
+        let primary = comparisons
+            .iter()
+            .filter(|c| c.target == targets[0])
+            .find_map(|c| c.result.as_ref().ok());
+        if let Some(t) = primary {
+            maybe_copy_to_clipboard(args.copy, &t.text).await;
+        }
+        drop(tmpdir);
+        return Ok(());
+    }
 
-    let (translation, detected_src) =
-        translate_deepl(&client, &api_key, &base, &ocr_text, &tgt, Some(&src)).await?;
+    let chain = backend_chain(&args, &config, default, extra_options, ocr.confidence).await?;
+    let translations = translate_all(&chain, &ocr_text, &src, &targets, protect, &args).await;
+    save_translation_log(artifacts.as_ref(), &chain, &args, &config);
+    let translations = translations?;
 
     // 5) Output + optional copy
-    let ocr_label = format!("{} / {}", src, ocr_pack); // show DeepL src + Tesseract pack
-    print_result(
-        &ocr_label,
-        &ocr_text,
-        &tgt,
-        &translation,
-        detected_src.as_deref(),
-    );
-    maybe_copy_to_clipboard(args.copy, &translation);
+    print_result(args.format, &ocr_label, &ocr_text, &translations);
+    maybe_copy_to_clipboard(args.copy, &clipboard_text(&translations, args.copy_all)).await;
 
     // keep tempdir alive until here
     drop(tmpdir);
     Ok(())
 }
+
+/// --edit and --confirm: lets the user fix the OCR text, then approve it.
+/// `None` when the user declines.
+async fn review(text: String, args: &Args) -> Result<Option<String>> {
+    let text = match args.edit {
+        true => edit_text(&text).await?,
+        false => text,
+    };
+    if args.confirm && !confirm(&text).await? {
+        return Ok(None);
+    }
+    Ok(Some(text))
+}
+
+/// --multi --join: the regions' texts in selection order, one per line.
+fn join_texts(texts: &[(Geometry, String)]) -> String {
+    texts
+        .iter()
+        .map(|(_, t)| t.trim())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Providers tried in order until one succeeds: --backend, else `chain` in
+/// config.toml, else the default DeepL account.
+async fn backend_chain(
+    args: &Args,
+    config: &Config,
+    default: impl Future<Output = Result<Backend>>,
+    options: DeeplOptions,
+    ocr_confidence: Option<f32>,
+) -> Result<Chain> {
+    let names = match (&args.backend, config.chain.is_empty()) {
+        (Some(names), _) => names.clone(),
+        (None, false) => config.chain.clone(),
+        (None, true) => vec![DEFAULT_BACKEND.to_string()],
+    };
+    let names = offline_backends(names, config, args.offline)?;
+    let chain =
+        Chain::new(build_backends(&names, config, default, options, args, ocr_confidence).await)?;
+    Ok(match args.keep_artifacts {
+        Some(_) => chain.recording(),
+        None => chain,
+    })
+}
+
+/// Writes the chain's requests and responses for --keep-artifacts.
+fn save_translation_log(
+    artifacts: Option<&Artifacts>,
+    chain: &Chain,
+    args: &Args,
+    config: &Config,
+) {
+    if let Some(a) = artifacts {
+        let log =
+            serde_json::to_string_pretty(&chain.take_log()).expect("JSON values always serialize");
+        a.save("translation.json", redact_secrets(log, args, config));
+    }
+}
+
+/// Blanks out every API key trein knows of.
+fn redact_secrets(mut text: String, args: &Args, config: &Config) -> String {
+    let mut keys: Vec<String> = resolve_deepl_api_key(args, config)
+        .ok()
+        .into_iter()
+        .collect();
+    keys.extend(config.deepl_api_keys.iter().cloned());
+    keys.extend(std::env::var("OPENAI_API_KEY").ok());
+    keys.extend(
+        config
+            .backends
+            .values()
+            .filter_map(|b| b.api_key().map(str::to_string)),
+    );
+    for key in keys {
+        text = redact(&text, &key);
+    }
+    text
+}
+
+/// Translates into every target at once, echoing the primary target's tokens
+/// from streaming backends, and adds back-translations when asked.
+async fn translate_all(
+    chain: &Chain,
+    text: &str,
+    src: &str,
+    targets: &[String],
+    protect: Option<&Protection>,
+    args: &Args,
+) -> Result<Vec<Translation>> {
+    let echo = StreamEcho::new(&targets[0]);
+    let push = |token: &str| echo.push(token);
+    let stream =
+        args.format == OutputFormat::Text && !args.no_stream && std::io::stderr().is_terminal();
+    let result = try_join_all(targets.iter().enumerate().map(|(i, tgt)| {
+        let on_token = (stream && i == 0).then_some(&push as &dyn Fn(&str));
+        chain.translate_streaming(text, src, tgt, protect, on_token)
+    }))
+    .await;
+    echo.finish();
+    let mut translations = result?;
+
+    // Optional quality signal: translate each result back and compare. A
+    // failure here only loses the signal, not the translations.
+    if args.back_translate {
+        let backs = join_all(
+            translations
+                .iter()
+                .map(|t| back_translate(chain, text, src, t, args.back_threshold, protect)),
+        )
+        .await;
+        for (t, back) in translations.iter_mut().zip(backs) {
+            match back {
+                Ok(back) => t.back = Some(back),
+                Err(e) => eprintln!("(Warning) could not back-translate {}: {e:#}", t.target),
+            }
+        }
+    }
+    Ok(translations)
+}
+
+/// The DeepL account from --deepl-api-key/$DEEPL_API_KEY/config, with the
+/// profile's options and glossary.
+async fn default_backend(
+    args: &Args,
+    config: &Config,
+    options: DeeplOptions,
+    glossary: Option<&str>,
+    src: &str,
+    targets: &[String],
+) -> Result<Backend> {
+    let mut backend = Backend::deepl(deepl_api(args, config)?, options);
+    if let Some(name) = glossary {
+        backend.use_glossary(name).await?;
+    }
+    // The glossary must fit at least one requested pair.
+    if let Some(g) = backend.glossary()
+        && !targets
+            .iter()
+            .any(|t| check_glossary_pair(g, src, t).is_ok())
+    {
+        check_glossary_pair(g, src, &targets[0])?;
+    }
+    Ok(backend)
+}
+
+/// Every backend --compare runs when none are named.
+fn configured_backends(config: &Config) -> Vec<String> {
+    let mut names = Vec::new();
+    if !config.backends.contains_key(DEFAULT_BACKEND) {
+        names.push(DEFAULT_BACKEND.to_string());
+    }
+    names.extend(config.backends.keys().cloned());
+    names
+}
+
+/// With --offline, drops backends that would reach the network and fails if
+/// nothing local is left.
+fn offline_backends(names: Vec<String>, config: &Config, offline: bool) -> Result<Vec<String>> {
+    if !offline {
+        return Ok(names);
+    }
+    let (local, network): (Vec<_>, Vec<_>) = names
+        .into_iter()
+        .partition(|n| !needs_network(n, &config.backends));
+    if local.is_empty() {
+        bail!(
+            "--offline refuses network backends ({}). Configure a `type = \"command\"` or local \
+             `type = \"libretranslate\"` backend and pick it with --backend.",
+            network.join(", ")
+        );
+    }
+    for name in &network {
+        eprintln!("(Offline) skipping {name}: it needs the network");
+    }
+    Ok(local)
+}
+
+/// Sets up the named backends in order, each kept even if it could not be
+/// set up so the caller decides how to report it. The default DeepL account
+/// is followed by one "deepl[N]" entry per extra key in `deepl_api_keys`.
+async fn build_backends(
+    names: &[String],
+    config: &Config,
+    default: impl Future<Output = Result<Backend>>,
+    options: DeeplOptions,
+    args: &Args,
+    ocr_confidence: Option<f32>,
+) -> Vec<(String, Result<Backend>)> {
+    let default_key = resolve_deepl_api_key(args, config).ok();
+    let client = http::client();
+    let mut default = Some(default);
+    let mut out = Vec::new();
+    for name in names {
+        let backend = match config.backends.get(name) {
+            Some(cfg) => {
+                let retry = retry_policy(args, config);
+                Backend::from_config(cfg, &client, &retry, default_key.as_deref(), ocr_confidence)
+                    .await
+            }
+            None if name == DEFAULT_BACKEND => match default.take() {
+                Some(fut) => fut.await,
+                None => continue,
+            },
+            None => Err(anyhow!("No backend named ‘{name}’ in config.toml")),
+        };
+        out.push((name.clone(), backend));
+        if name == DEFAULT_BACKEND && !config.backends.contains_key(name) {
+            for (i, key) in config.deepl_api_keys.iter().enumerate() {
+                // --deepl-endpoint and $DEEPL_API_BASE apply to every key.
+                let backend = resolve_deepl_endpoint(args, key).map(|endpoint| {
+                    let api = DeeplApi {
+                        client: client.clone(),
+                        retry: retry_policy(args, config),
+                        base_url: endpoint.url().to_string(),
+                        api_key: key.clone(),
+                    };
+                    Backend::deepl(api, options.clone())
+                });
+                out.push((format!("{DEFAULT_BACKEND}[{}]", i + 2), backend));
+            }
+        }
+    }
+    out
+}
+
+fn retry_policy(args: &Args, config: &Config) -> RetryPolicy {
+    config.retry.policy(args.retries, args.retry_deadline)
+}
+
+fn deepl_api(args: &Args, config: &Config) -> Result<DeeplApi> {
+    let api_key = resolve_deepl_api_key(args, config)?;
+    let endpoint = resolve_deepl_endpoint(args, &api_key)?;
+    if args.verbose {
+        eprintln!("DeepL endpoint: {} ({})", endpoint.label(), endpoint.url());
+        eprintln!("DeepL key: {}", mask_key(&api_key));
+    }
+    Ok(DeeplApi {
+        client: http::client(),
+        retry: retry_policy(args, config),
+        base_url: endpoint.url().to_string(),
+        api_key,
+    })
+}
@@ -1,57 +1,88 @@
 use anyhow::{Context, Result, anyhow, bail};
-use std::path::{Path, PathBuf};
-use std::process::Command;
-use tempfile::{TempDir, tempdir};
+use std::path::Path;
 
-pub fn select_region() -> Result<String> {
-    let out = Command::new("slurp")
-        .args(["-f", "%x,%y %wx%h"])
-        .output()
-        .context("Failed to run `slurp` (is it installed?)")?;
+use crate::artifacts::Artifacts;
+use crate::timeouts::Stage;
+use crate::tools;
 
-    if !out.status.success() {
-        bail!("Selection cancelled or `slurp` failed.");
-    }
-    let geometry = String::from_utf8_lossy(&out.stdout).trim().to_string();
-    if geometry.is_empty() {
-        bail!("No selection geometry received from `slurp`.");
-    }
-    Ok(geometry)
-}
-
-pub fn capture_region(geometry: &str) -> Result<(TempDir, PathBuf)> {
-    let tmpdir = tempdir().context("Could not create temp dir")?;
-    let png_path = tmpdir.path().join("capture.png");
-    let png_path_str = png_path.to_string_lossy().to_string();
-
-    let status = Command::new("grim")
-        .args(["-g", geometry, &png_path_str])
-        .status()
-        .context("Failed to run `grim` (is it installed?)")?;
-    if !status.success() {
-        bail!("`grim` failed to capture the region.");
-    }
-    Ok((tmpdir, png_path))
+/// Recognised text plus Tesseract's mean word confidence (0–100).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ocr {
+    pub text: String,
+    pub confidence: Option<f32>,
 }
 
-pub fn ocr_image(png_path: &Path, ocr_lang: &str) -> Result<String> {
+/// OCRs `png_path`. With `artifacts`, Tesseract's thresholded input image,
+/// its raw TSV and the text before and after tidying are kept too.
+pub async fn ocr_image(
+    png_path: &Path,
+    ocr_lang: &str,
+    artifacts: Option<&Artifacts>,
+) -> Result<Ocr> {
     let png = png_path
         .to_str()
         .ok_or_else(|| anyhow!("Screenshot path not valid UTF-8"))?
         .to_string();
 
-    let out = Command::new("tesseract")
-        .args([&png, "stdout", "-l", ocr_lang])
-        .output()
-        .context("Failed to run `tesseract` (is it installed, with language data?)")?;
+    let mut command = tools::command("tesseract");
+    command.args([&png, "stdout", "-l", ocr_lang]);
+    if let Some(a) = artifacts {
+        // Written as tessinput.tif in the working directory.
+        command
+            .args(["-c", "tessedit_write_images=true"])
+            .current_dir(a.dir());
+    }
+    command.arg("tsv");
+    let out = tools::run(command, "tesseract", None, Stage::Ocr)
+        .await
+        .context("Tesseract failed (is it installed, with language data?)")?;
 
     if !out.status.success() {
         let stderr = String::from_utf8_lossy(&out.stderr);
         bail!("Tesseract failed: {stderr}");
     }
 
-    let raw = String::from_utf8_lossy(&out.stdout).to_string();
-    Ok(tidy_ocr(&raw))
+    let tsv = String::from_utf8_lossy(&out.stdout);
+    let (raw, confidence) = parse_tsv(&tsv);
+    let text = tidy_ocr(&raw);
+    if let Some(a) = artifacts {
+        a.keep("preprocessed.tif", &a.dir().join("tessinput.tif"), false);
+        a.save("ocr.tsv", tsv.as_bytes());
+        a.save("ocr-raw.txt", &raw);
+        a.save("ocr.txt", &text);
+    }
+    Ok(Ocr { text, confidence })
+}
+
+/// Rebuilds the plain text (one line per Tesseract line) from `tsv` output
+/// and averages the word confidences.
+fn parse_tsv(tsv: &str) -> (String, Option<f32>) {
+    let mut text = String::new();
+    let mut line_key = None;
+    let mut confidences = Vec::new();
+    // level page block par line word left top width height conf text
+    for row in tsv.lines().skip(1) {
+        let cols: Vec<&str> = row.splitn(12, '\t').collect();
+        if cols.len() < 12 || cols[0] != "5" || cols[11].trim().is_empty() {
+            continue;
+        }
+        let key = (cols[2], cols[3], cols[4]);
+        match line_key {
+            None => {}
+            Some(k) if k == key => text.push(' '),
+            Some(_) => text.push('\n'),
+        }
+        line_key = Some(key);
+        text.push_str(cols[11].trim());
+        if let Ok(c) = cols[10].parse::<f32>()
+            && c >= 0.0
+        {
+            confidences.push(c);
+        }
+    }
+    let confidence = (!confidences.is_empty())
+        .then(|| confidences.iter().sum::<f32>() / confidences.len() as f32);
+    (text, confidence)
 }
 
 fn tidy_ocr(s: &str) -> String {
@@ -72,4 +103,18 @@ mod tests {
         let got = tidy_ocr(s);
         assert_eq!(got, "hyphenated text with spaces ok");
     }
+
+    #[test]
+    fn tsv_lines_and_confidence() {
+        let tsv = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext\n\
+1\t1\t0\t0\t0\t0\t0\t0\t100\t40\t-1\t\n\
+5\t1\t1\t1\t1\t1\t0\t0\t10\t10\t90\thy-\n\
+5\t1\t1\t1\t2\t1\t0\t20\t10\t10\t80\tphen\n\
+5\t1\t1\t1\t2\t2\t20\t20\t10\t10\t70\tword\n";
+        let (raw, confidence) = parse_tsv(tsv);
+        assert_eq!(raw, "hy-\nphen word");
+        assert_eq!(tidy_ocr(&raw), "hyphen word");
+        assert_eq!(confidence, Some(80.0));
+        assert_eq!(parse_tsv("level\n"), (String::new(), None));
+    }
 }
@@ -1,49 +1,298 @@
+use serde_json::json;
+use std::cell::Cell;
+use std::io::Write;
+
+use crate::backend::Comparison;
+use crate::cli::OutputFormat;
+use crate::region::Geometry;
+use crate::translate::Translation;
+
 pub fn print_result(
+    format: OutputFormat,
     ocr_lang: &str,
     ocr_text: &str,
-    target: &str,
-    translation: &str,
-    detected_src: Option<&str>,
+    translations: &[Translation],
 ) {
-    println!(
-        "{}",
-        render_result(ocr_lang, ocr_text, target, translation, detected_src)
-    );
+    match format {
+        OutputFormat::Text => println!("{}", render_result(ocr_lang, ocr_text, translations)),
+        OutputFormat::Json => println!("{}", render_json(ocr_lang, ocr_text, translations)),
+    }
 }
 
-pub fn render_result(
-    ocr_lang: &str,
-    ocr_text: &str,
-    target: &str,
-    translation: &str,
-    detected_src: Option<&str>,
-) -> String {
+pub fn render_result(ocr_lang: &str, ocr_text: &str, translations: &[Translation]) -> String {
     let mut s = String::new();
     s.push_str(&format!(
         "=== OCR (lang: {}) ===\n{}\n\n",
         ocr_lang,
         ocr_text.trim()
     ));
-    match detected_src {
-        Some(src) => s.push_str(&format!(
-            "=== Translation → {} (detected: {}) ===\n{}\n\n",
-            target, src, translation
-        )),
-        _ => s.push_str(&format!(
-            "=== Translation → {} ===\n{}\n\n",
-            target, translation
-        )),
+    render_translations(&mut s, translations);
+    s
+}
+
+fn render_translations(s: &mut String, translations: &[Translation]) {
+    for t in translations {
+        let via = t
+            .provider
+            .as_deref()
+            .map(|p| format!(" [via {p}]"))
+            .unwrap_or_default();
+        match t.detected_source.as_deref() {
+            Some(src) => s.push_str(&format!(
+                "=== Translation → {} (detected: {}){} ===\n{}\n\n",
+                t.target, src, via, t.text
+            )),
+            _ => s.push_str(&format!(
+                "=== Translation → {}{} ===\n{}\n\n",
+                t.target, via, t.text
+            )),
+        }
+        if let Some(back) = &t.back {
+            let flag = if back.below_threshold { ", LOW" } else { "" };
+            s.push_str(&format!(
+                "=== Back-translation ← {} (similarity: {:.2}{}) ===\n{}\n\n",
+                t.target, back.similarity, flag, back.text
+            ));
+        }
+    }
+}
+
+pub fn render_json(ocr_lang: &str, ocr_text: &str, translations: &[Translation]) -> String {
+    let value = json!({
+        "ocr": { "lang": ocr_lang, "text": ocr_text.trim() },
+        "translations": translations,
+    });
+    serde_json::to_string_pretty(&value).expect("JSON values always serialize")
+}
+
+/// --dry-run: the OCR text of each region and the characters that
+/// translating it into every target would be billed.
+pub fn print_dry_run(format: OutputFormat, ocr_lang: &str, texts: &[String], targets: &[String]) {
+    match format {
+        OutputFormat::Text => println!("{}", render_dry_run(ocr_lang, texts, targets)),
+        OutputFormat::Json => println!("{}", render_dry_run_json(ocr_lang, texts, targets)),
+    }
+}
+
+/// DeepL bills each Unicode character of the source once per target.
+fn billed_characters(texts: &[String]) -> usize {
+    texts.iter().map(|t| t.trim().chars().count()).sum()
+}
+
+pub fn render_dry_run(ocr_lang: &str, texts: &[String], targets: &[String]) -> String {
+    let mut s = String::new();
+    for text in texts {
+        s.push_str(&format!(
+            "=== OCR (lang: {}) ===\n{}\n\n",
+            ocr_lang,
+            text.trim()
+        ));
     }
+    let chars = billed_characters(texts);
+    s.push_str(&format!(
+        "=== Dry run: not translated ===\n{} characters × {} target(s) ({}) = {} billed\n",
+        chars,
+        targets.len(),
+        targets.join(", "),
+        chars * targets.len()
+    ));
     s
 }
 
+pub fn render_dry_run_json(ocr_lang: &str, texts: &[String], targets: &[String]) -> String {
+    let chars = billed_characters(texts);
+    let value = json!({
+        "ocr": { "lang": ocr_lang, "texts": texts.iter().map(|t| t.trim()).collect::<Vec<_>>() },
+        "dry_run": {
+            "characters": chars,
+            "targets": targets,
+            "billed_characters": chars * targets.len(),
+        },
+    });
+    serde_json::to_string_pretty(&value).expect("JSON values always serialize")
+}
+
+/// One region of a --multi capture and its translations.
+pub struct Segment {
+    pub region: Geometry,
+    pub text: String,
+    pub translations: Vec<Translation>,
+}
+
+pub fn print_segments(format: OutputFormat, ocr_lang: &str, segments: &[Segment]) {
+    match format {
+        OutputFormat::Text => println!("{}", render_segments(ocr_lang, segments)),
+        OutputFormat::Json => println!("{}", render_segments_json(ocr_lang, segments)),
+    }
+}
+
+/// Each region's text followed by its translations, in selection order.
+pub fn render_segments(ocr_lang: &str, segments: &[Segment]) -> String {
+    let mut s = String::new();
+    for (i, seg) in segments.iter().enumerate() {
+        s.push_str(&format!(
+            "=== Region {} ({}) — OCR (lang: {}) ===\n{}\n\n",
+            i + 1,
+            seg.region,
+            ocr_lang,
+            seg.text.trim()
+        ));
+        render_translations(&mut s, &seg.translations);
+    }
+    s
+}
+
+pub fn render_segments_json(ocr_lang: &str, segments: &[Segment]) -> String {
+    let segments: Vec<_> = segments
+        .iter()
+        .map(|seg| {
+            json!({
+                "region": seg.region.to_string(),
+                "text": seg.text.trim(),
+                "translations": seg.translations,
+            })
+        })
+        .collect();
+    let value = json!({ "ocr": { "lang": ocr_lang }, "segments": segments });
+    serde_json::to_string_pretty(&value).expect("JSON values always serialize")
+}
+
+pub fn print_comparison(
+    format: OutputFormat,
+    ocr_lang: &str,
+    ocr_text: &str,
+    comparisons: &[Comparison],
+) {
+    match format {
+        OutputFormat::Text => println!("{}", render_comparison(ocr_lang, ocr_text, comparisons)),
+        OutputFormat::Json => println!(
+            "{}",
+            render_comparison_json(ocr_lang, ocr_text, comparisons)
+        ),
+    }
+}
+
+/// Groups results by target so the backends' versions sit next to each other.
+pub fn render_comparison(ocr_lang: &str, ocr_text: &str, comparisons: &[Comparison]) -> String {
+    let mut s = format!("=== OCR (lang: {}) ===\n{}\n\n", ocr_lang, ocr_text.trim());
+    let mut targets: Vec<&str> = comparisons.iter().map(|c| c.target.as_str()).collect();
+    targets.dedup();
+    for target in targets {
+        s.push_str(&format!("=== Compare → {} ===\n", target));
+        for c in comparisons.iter().filter(|c| c.target == target) {
+            let ms = c.latency.as_millis();
+            match &c.result {
+                Ok(t) => s.push_str(&format!("[{}] ({} ms)\n{}\n\n", c.backend, ms, t.text)),
+                Err(e) => s.push_str(&format!(
+                    "[{}] (failed after {} ms)\n{}\n\n",
+                    c.backend, ms, e
+                )),
+            }
+        }
+    }
+    s
+}
+
+pub fn render_comparison_json(
+    ocr_lang: &str,
+    ocr_text: &str,
+    comparisons: &[Comparison],
+) -> String {
+    let results: Vec<_> = comparisons
+        .iter()
+        .map(|c| {
+            let mut v = json!({
+                "backend": c.backend,
+                "target": c.target,
+                "latency_ms": c.latency.as_millis() as u64,
+            });
+            match &c.result {
+                Ok(t) => {
+                    v["text"] = json!(t.text);
+                    v["detected_source"] = json!(t.detected_source);
+                }
+                Err(e) => v["error"] = json!(e),
+            }
+            v
+        })
+        .collect();
+    let value = json!({
+        "ocr": { "lang": ocr_lang, "text": ocr_text.trim() },
+        "comparisons": results,
+    });
+    serde_json::to_string_pretty(&value).expect("JSON values always serialize")
+}
+
+/// Echoes tokens from a streaming backend to stderr until the final result
+/// is printed.
+pub struct StreamEcho {
+    target: String,
+    started: Cell<bool>,
+}
+
+impl StreamEcho {
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            started: Cell::new(false),
+        }
+    }
+
+    pub fn push(&self, token: &str) {
+        if !self.started.replace(true) {
+            eprintln!("--- {} (streaming) ---", self.target);
+        }
+        eprint!("{token}");
+        let _ = std::io::stderr().flush();
+    }
+
+    pub fn finish(&self) {
+        if self.started.get() {
+            eprintln!("\n");
+        }
+    }
+}
+
+/// The primary (first) translation, or every translation labelled by target.
+pub fn clipboard_text(translations: &[Translation], all: bool) -> String {
+    match translations {
+        [only] => only.text.clone(),
+        [first, ..] if !all => first.text.clone(),
+        _ => translations
+            .iter()
+            .map(|t| format!("[{}] {}", t.target, t.text))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// One line per region, as `clipboard_text` would copy it.
+pub fn segments_clipboard_text(segments: &[Segment], all: bool) -> String {
+    segments
+        .iter()
+        .map(|seg| clipboard_text(&seg.translations, all))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backtranslate::BackTranslation;
+
+    fn tr(target: &str, text: &str, detected: Option<&str>) -> Translation {
+        Translation {
+            target: target.into(),
+            text: text.into(),
+            detected_source: detected.map(Into::into),
+            provider: None,
+            back: None,
+        }
+    }
 
     #[test]
     fn render_with_detected_source() {
-        let out = render_result("EN / eng", "hello", "FR", "bonjour", Some("EN"));
+        let out = render_result("EN / eng", "hello", &[tr("FR", "bonjour", Some("EN"))]);
         assert!(out.contains("=== OCR (lang: EN / eng) ==="));
         assert!(out.contains("hello"));
         assert!(out.contains("Translation → FR (detected: EN)"));
@@ -52,9 +301,128 @@ mod tests {
 
     #[test]
     fn render_without_detected_source() {
-        let out = render_result("ZH / chi_sim", "你好", "EN-GB", "hello", None);
+        let out = render_result("ZH / chi_sim", "你好", &[tr("EN-GB", "hello", None)]);
         assert!(out.contains("=== OCR (lang: ZH / chi_sim) ==="));
         assert!(out.contains("Translation → EN-GB ==="));
         assert!(out.contains("hello"));
     }
+
+    #[test]
+    fn render_one_section_per_target() {
+        let translations = [tr("PL", "cześć", None), tr("DE", "hallo", None)];
+        let out = render_result("EN / eng", "hi", &translations);
+        let pl = out.find("Translation → PL").unwrap();
+        let de = out.find("Translation → DE").unwrap();
+        assert!(pl < de);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_json("EN / eng", "hi", &translations)).unwrap();
+        assert_eq!(json["ocr"]["text"], "hi");
+        assert_eq!(json["translations"][1]["target"], "DE");
+        assert_eq!(json["translations"][1]["text"], "hallo");
+    }
+
+    #[test]
+    fn comparison_shows_latency_and_errors() {
+        use std::time::Duration;
+        let comparisons = [
+            Comparison {
+                backend: "deepl".into(),
+                target: "DE".into(),
+                latency: Duration::from_millis(120),
+                result: Ok(tr("DE", "Hallo", None)),
+            },
+            Comparison {
+                backend: "backup".into(),
+                target: "DE".into(),
+                latency: Duration::from_millis(30),
+                result: Err("HTTP 456".into()),
+            },
+        ];
+        let out = render_comparison("EN / eng", "Hello", &comparisons);
+        assert!(out.contains("=== Compare → DE ==="));
+        assert!(out.contains("[deepl] (120 ms)\nHallo"));
+        assert!(out.contains("[backup] (failed after 30 ms)\nHTTP 456"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_comparison_json("EN / eng", "Hello", &comparisons))
+                .unwrap();
+        assert_eq!(json["comparisons"][0]["latency_ms"], 120);
+        assert_eq!(json["comparisons"][1]["error"], "HTTP 456");
+    }
+
+    #[test]
+    fn render_reports_provider() {
+        let mut t = tr("DE", "Hallo", Some("EN"));
+        t.provider = Some("deepl-backup".into());
+        let out = render_result("EN / eng", "Hello", std::slice::from_ref(&t));
+        assert!(out.contains("=== Translation → DE (detected: EN) [via deepl-backup] ==="));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_json("EN / eng", "Hello", &[t])).unwrap();
+        assert_eq!(json["translations"][0]["provider"], "deepl-backup");
+    }
+
+    #[test]
+    fn render_back_translation_with_flag() {
+        let mut t = tr("DE", "Tür auf", None);
+        t.back = Some(BackTranslation {
+            text: "Door open".into(),
+            similarity: 0.42,
+            below_threshold: true,
+        });
+        let out = render_result("EN / eng", "Open the door", &[t]);
+        assert!(out.contains("=== Back-translation ← DE (similarity: 0.42, LOW) ===\nDoor open"));
+    }
+
+    #[test]
+    fn segments_keep_selection_order() {
+        let segments = [
+            Segment {
+                region: "0,0 100x20".parse().unwrap(),
+                text: "アリス".into(),
+                translations: vec![tr("EN", "Alice", None)],
+            },
+            Segment {
+                region: "0,30 400x80".parse().unwrap(),
+                text: "こんにちは".into(),
+                translations: vec![tr("EN", "Hello", None)],
+            },
+        ];
+        let out = render_segments("JA / jpn", &segments);
+        assert!(out.starts_with("=== Region 1 (0,0 100x20) — OCR (lang: JA / jpn) ===\nアリス"));
+        assert!(out.find("Alice").unwrap() < out.find("=== Region 2 (0,30 400x80)").unwrap());
+        assert!(out.contains("=== Translation → EN ===\nHello"));
+        assert_eq!(segments_clipboard_text(&segments, false), "Alice\nHello");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_segments_json("JA / jpn", &segments)).unwrap();
+        assert_eq!(json["segments"][1]["region"], "0,30 400x80");
+        assert_eq!(json["segments"][1]["translations"][0]["text"], "Hello");
+    }
+
+    #[test]
+    fn clipboard_primary_or_all() {
+        let translations = [tr("PL", "cześć", None), tr("DE", "hallo", None)];
+        assert_eq!(clipboard_text(&translations, false), "cześć");
+        assert_eq!(
+            clipboard_text(&translations, true),
+            "[PL] cześć\n[DE] hallo"
+        );
+        assert_eq!(clipboard_text(&translations[1..], true), "hallo");
+    }
+
+    #[test]
+    fn dry_run_counts_characters_per_target() {
+        let texts = vec!["こんにちは ".to_string(), "hi".to_string()];
+        let targets = vec!["EN".to_string(), "DE".to_string()];
+        let out = render_dry_run("JA / jpn", &texts, &targets);
+        assert!(out.contains("=== OCR (lang: JA / jpn) ===\nこんにちは\n"));
+        assert!(out.contains("7 characters × 2 target(s) (EN, DE) = 14 billed"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_dry_run_json("JA / jpn", &texts, &targets)).unwrap();
+        assert_eq!(json["ocr"]["texts"][1], "hi");
+        assert_eq!(json["dry_run"]["billed_characters"], 14);
+    }
 }
@@ -0,0 +1,364 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use crate::translate::deepl_source_for_target;
+
+// URLs, e-mail addresses, version strings, snake_case and camelCase identifiers.
+const BUILTIN_PATTERNS: &[&str] = &[
+    r"\bhttps?://[^\s<>]+[^\s<>.,;:!?)\]]",
+    r"\b[\w.+-]+@[\w-]+(?:\.[\w-]+)+\b",
+    r"\bv?\d+(?:\.\d+){1,3}(?:-[0-9A-Za-z.]+)?\b",
+    r"\b[a-z][a-z0-9]*(?:_[a-z0-9]+)+\b",
+    r"\b[a-z]+(?:[A-Z][a-z0-9]*)+\b",
+];
+
+/// A placeholder as sent, or as a translator may space it out.
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*(\d+)\s*\}\}").expect("static regex"));
+
+/// `[protect]` in config.toml.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProtectConfig {
+    /// Masking is opt-in: it rewrites what the translator sees.
+    pub enabled: bool,
+    /// Protect URLs, e-mails, versions and code identifiers out of the box.
+    pub builtin: bool,
+    /// Extra regexes kept verbatim for every language pair.
+    pub patterns: Vec<String>,
+    /// Per-language-pair glossaries and patterns.
+    pub pairs: Vec<PairConfig>,
+}
+
+impl Default for ProtectConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            builtin: true,
+            patterns: Vec::new(),
+            pairs: Vec::new(),
+        }
+    }
+}
+
+/// `[[protect.pairs]]`: `target` matches either exactly or by base language.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PairConfig {
+    pub source: String,
+    pub target: String,
+    /// TSV file of `source<TAB>target` terms.
+    pub glossary: Option<PathBuf>,
+    pub patterns: Vec<String>,
+}
+
+impl PairConfig {
+    fn matches(&self, source: &str, target: &str) -> bool {
+        let base = deepl_source_for_target(target).unwrap_or_default();
+        self.source.eq_ignore_ascii_case(source)
+            && (self.target.eq_ignore_ascii_case(target) || self.target.eq_ignore_ascii_case(&base))
+    }
+}
+
+/// The run's protectors, built from `[protect]` once per language pair.
+#[derive(Debug, Default)]
+pub struct Protection {
+    config: ProtectConfig,
+    built: Mutex<HashMap<(String, String), Arc<Protector>>>,
+}
+
+impl Protection {
+    pub fn new(config: ProtectConfig) -> Self {
+        Self {
+            config,
+            built: Mutex::default(),
+        }
+    }
+
+    /// The protector for `source`→`target`, or `None` if disabled.
+    pub fn for_pair(&self, source: &str, target: &str) -> Result<Option<Arc<Protector>>> {
+        let key = (source.to_string(), target.to_string());
+        let mut built = self.built.lock().expect("protector lock");
+        if let Some(p) = built.get(&key) {
+            return Ok(Some(p.clone()));
+        }
+        let Some(p) = Protector::from_config(&self.config, source, target)? else {
+            return Ok(None);
+        };
+        let p = Arc::new(p);
+        built.insert(key, p.clone());
+        Ok(Some(p))
+    }
+}
+
+/// Replaces protected spans with numbered placeholders before translation
+/// and puts the originals (or glossary targets) back afterwards.
+#[derive(Debug, Default)]
+pub struct Protector {
+    patterns: Vec<Regex>,
+    terms: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub struct Masked {
+    pub text: String,
+    replacements: Vec<String>,
+}
+
+impl Protector {
+    pub fn new(patterns: &[String], mut terms: Vec<(String, String)>) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Regex::new(p).with_context(|| format!("Invalid protect pattern: {p}")))
+            .collect::<Result<Vec<_>>>()?;
+        // Longest first so "Foo Pro" wins over "Foo".
+        terms.sort_by_key(|t| std::cmp::Reverse(t.0.len()));
+        Ok(Self { patterns, terms })
+    }
+
+    /// Builds the protector for one language pair, or `None` if disabled.
+    pub fn from_config(cfg: &ProtectConfig, source: &str, target: &str) -> Result<Option<Self>> {
+        if !cfg.enabled {
+            return Ok(None);
+        }
+        let mut patterns: Vec<String> = Vec::new();
+        if cfg.builtin {
+            patterns.extend(BUILTIN_PATTERNS.iter().map(|p| p.to_string()));
+        }
+        patterns.extend(cfg.patterns.iter().cloned());
+        let mut terms = Vec::new();
+        for pair in cfg.pairs.iter().filter(|p| p.matches(source, target)) {
+            patterns.extend(pair.patterns.iter().cloned());
+            if let Some(path) = &pair.glossary {
+                terms.extend(load_terms(&expand_home(path))?);
+            }
+        }
+        Ok(Some(Self::new(&patterns, terms)?))
+    }
+
+    pub fn mask(&self, text: &str) -> Masked {
+        // (start, end, replacement)
+        let mut spans: Vec<(usize, usize, String)> = Vec::new();
+        for (src, tgt) in &self.terms {
+            for (i, _) in text.match_indices(src.as_str()) {
+                if is_whole_word(text, i, i + src.len()) {
+                    spans.push((i, i + src.len(), tgt.clone()));
+                }
+            }
+        }
+        // Placeholder look-alikes already in the text are masked as themselves,
+        // so restoring can't mistake them for ours.
+        for re in self.patterns.iter().chain([&*PLACEHOLDER]) {
+            for m in re.find_iter(text) {
+                spans.push((m.start(), m.end(), m.as_str().to_string()));
+            }
+        }
+        // Earliest first; on ties the longest span wins. Overlaps are dropped.
+        spans.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+        let mut out = String::with_capacity(text.len());
+        let mut replacements = Vec::new();
+        let mut pos = 0;
+        for (start, end, replacement) in spans {
+            if start < pos {
+                continue;
+            }
+            out.push_str(&text[pos..start]);
+            out.push_str(&placeholder(replacements.len()));
+            replacements.push(replacement);
+            pos = end;
+        }
+        out.push_str(&text[pos..]);
+        Masked {
+            text: out,
+            replacements,
+        }
+    }
+}
+
+impl Masked {
+    /// Substitutes placeholders in `translated`. Also returns the indices of
+    /// placeholders the translator dropped.
+    pub fn restore(&self, translated: &str) -> (String, Vec<usize>) {
+        let mut seen = vec![false; self.replacements.len()];
+        let restored = PLACEHOLDER.replace_all(translated, |caps: &regex::Captures| {
+            let idx: usize = caps[1].parse().unwrap_or(usize::MAX);
+            match self.replacements.get(idx) {
+                Some(r) => {
+                    seen[idx] = true;
+                    r.clone()
+                }
+                None => caps[0].to_string(),
+            }
+        });
+        let missing = seen
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !**s)
+            .map(|(i, _)| i)
+            .collect();
+        (restored.into_owned(), missing)
+    }
+}
+
+/// Whether `text[start..end]` doesn't continue a word on either side, so a
+/// term isn't masked inside a longer word.
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let term = &text[start..end];
+    let joins = |edge: Option<char>, outside: Option<char>| {
+        edge.is_some_and(is_word) && outside.is_some_and(is_word)
+    };
+    !joins(term.chars().next(), text[..start].chars().next_back())
+        && !joins(term.chars().next_back(), text[end..].chars().next())
+}
+
+fn placeholder(i: usize) -> String {
+    format!("{{{{{i}}}}}")
+}
+
+fn expand_home(path: &std::path::Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var("HOME")) {
+        (Ok(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Reads `source<TAB>target` lines; blank lines and `#` comments are skipped.
+pub fn load_terms(path: &std::path::Path) -> Result<Vec<(String, String)>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read glossary {}", path.display()))?;
+    Ok(parse_terms(&content))
+}
+
+fn parse_terms(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+        .filter_map(|l| {
+            let (src, tgt) = l.split_once('\t')?;
+            let src = src.trim();
+            (!src.is_empty()).then(|| (src.to_string(), tgt.trim().to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builtin() -> Protector {
+        let cfg = ProtectConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        Protector::from_config(&cfg, "EN", "DE").unwrap().unwrap()
+    }
+
+    #[test]
+    fn protection_is_opt_in() {
+        let cfg: ProtectConfig = toml::from_str("").unwrap();
+        assert!(Protector::from_config(&cfg, "EN", "DE").unwrap().is_none());
+        let cfg: ProtectConfig = toml::from_str("enabled = true").unwrap();
+        assert!(cfg.builtin);
+        assert!(Protector::from_config(&cfg, "EN", "DE").unwrap().is_some());
+    }
+
+    #[test]
+    fn builtin_patterns_round_trip() {
+        let text = "See https://example.com/a?b=1, mail ops@example.org, upgrade to v2.3.1 and set max_retries.";
+        let masked = builtin().mask(text);
+        assert!(!masked.text.contains("example"));
+        assert!(!masked.text.contains("2.3.1"));
+        assert!(!masked.text.contains("max_retries"));
+
+        let translated = masked
+            .text
+            .replace("See", "Siehe")
+            .replace("{{1}}", "{{ 1 }}");
+        let (restored, missing) = masked.restore(&translated);
+        assert!(missing.is_empty());
+        assert!(restored.starts_with("Siehe https://example.com/a?b=1, "));
+        assert!(restored.contains("ops@example.org"));
+        assert!(restored.contains("v2.3.1"));
+        assert!(restored.contains("max_retries"));
+    }
+
+    #[test]
+    fn glossary_terms_are_substituted_longest_first() {
+        let terms =
+            parse_terms("# product terms\nTrein\tTrein\nTrein Pro\tTrein Profi\n\nbad line\n");
+        let p = Protector::new(&[], terms).unwrap();
+        let masked = p.mask("Trein Pro beats Trein.");
+        assert_eq!(masked.text, "{{0}} beats {{1}}.");
+        let (restored, missing) = masked.restore("{{0}} schlägt {{1}}.");
+        assert_eq!(restored, "Trein Profi schlägt Trein.");
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn terms_only_match_whole_words() {
+        let p = Protector::new(&[], vec![("Trein".into(), "Trein".into())]).unwrap();
+        assert_eq!(
+            p.mask("Treinen, ReTrein and Trein_x, but (Trein).").text,
+            "Treinen, ReTrein and Trein_x, but ({{0}})."
+        );
+        let p = Protector::new(&[], vec![("C++".into(), "C++".into())]).unwrap();
+        assert_eq!(p.mask("C++17 and C++.").text, "{{0}}17 and {{1}}.");
+    }
+
+    #[test]
+    fn literal_placeholders_survive() {
+        let p = Protector::new(&[], vec![("Trein".into(), "Trein".into())]).unwrap();
+        let masked = p.mask("Trein fills {{0}} and {{ 7 }}.");
+        assert_eq!(masked.text, "{{0}} fills {{1}} and {{2}}.");
+        let (restored, missing) = masked.restore("{{0}} füllt {{1}} und {{2}}.");
+        assert_eq!(restored, "Trein füllt {{0}} und {{ 7 }}.");
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn protectors_are_built_once_per_pair() {
+        let protection = Protection::new(ProtectConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        let de = protection.for_pair("EN", "DE").unwrap().unwrap();
+        assert!(Arc::ptr_eq(
+            &de,
+            &protection.for_pair("EN", "DE").unwrap().unwrap()
+        ));
+        assert!(!Arc::ptr_eq(
+            &de,
+            &protection.for_pair("EN", "FR").unwrap().unwrap()
+        ));
+        assert!(
+            Protection::default()
+                .for_pair("EN", "DE")
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn dropped_placeholders_are_reported() {
+        let masked = builtin().mask("Version 1.2 and 3.4");
+        let (_, missing) = masked.restore("Version {{0}}");
+        assert_eq!(missing, vec![1]);
+    }
+
+    #[test]
+    fn pair_matching_uses_base_target() {
+        let pair = PairConfig {
+            source: "ja".into(),
+            target: "EN".into(),
+            ..Default::default()
+        };
+        assert!(pair.matches("JA", "EN-GB"));
+        assert!(!pair.matches("JA", "DE"));
+    }
+}
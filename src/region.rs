@@ -0,0 +1,220 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::capture::{CaptureConfig, Capturer};
+use crate::cli::RegionCommand;
+use crate::state::{state_file, write_state_file};
+
+const REGIONS_FILE: &str = "regions.toml";
+
+/// A screen region in slurp/grim's `x,y WxH` notation (global compositor
+/// coordinates, so x and y may be negative on multi-monitor setups).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Geometry {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid =
+            || anyhow!("Invalid geometry ‘{s}’ (expected \"x,y WxH\", e.g. \"10,20 640x120\")");
+        let (pos, size) = s.trim().split_once(' ').ok_or_else(invalid)?;
+        let (x, y) = pos.split_once(',').ok_or_else(invalid)?;
+        let (w, h) = size.trim().split_once('x').ok_or_else(invalid)?;
+        let g = Geometry {
+            x: x.trim().parse().map_err(|_| invalid())?,
+            y: y.trim().parse().map_err(|_| invalid())?,
+            width: w.trim().parse().map_err(|_| invalid())?,
+            height: h.trim().parse().map_err(|_| invalid())?,
+        };
+        if g.width == 0 || g.height == 0 {
+            bail!("Geometry ‘{s}’ is empty");
+        }
+        Ok(g)
+    }
+}
+
+impl Geometry {
+    fn contains(&self, x: i64, y: i64) -> bool {
+        x >= self.x as i64
+            && y >= self.y as i64
+            && x < self.x as i64 + self.width as i64
+            && y < self.y as i64 + self.height as i64
+    }
+
+    fn corners(&self) -> [(i64, i64); 4] {
+        let (l, t) = (self.x as i64, self.y as i64);
+        let (r, b) = (l + self.width as i64 - 1, t + self.height as i64 - 1);
+        [(l, t), (r, t), (l, b), (r, b)]
+    }
+}
+
+impl fmt::Display for Geometry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{} {}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// The region captured last time, if any.
+pub fn load_last_region() -> Result<Geometry> {
+    let path = state_file("last-region")?;
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("No previous region yet ({} is missing)", path.display()))?;
+    content
+        .parse()
+        .with_context(|| format!("Corrupt region in {}", path.display()))
+}
+
+pub fn save_last_region(geometry: &Geometry) -> Result<()> {
+    write_state_file("last-region", &format!("{geometry}\n"))
+}
+
+/// Saved regions by name (`regions.toml` in the state dir).
+pub fn load_regions() -> Result<BTreeMap<String, Geometry>> {
+    let path = state_file(REGIONS_FILE)?;
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok(BTreeMap::new());
+    };
+    parse_regions(&content).with_context(|| format!("Invalid regions file {}", path.display()))
+}
+
+fn parse_regions(content: &str) -> Result<BTreeMap<String, Geometry>> {
+    let raw: BTreeMap<String, String> = toml::from_str(content)?;
+    raw.into_iter()
+        .map(|(name, g)| Ok((name, g.parse()?)))
+        .collect()
+}
+
+fn save_regions(regions: &BTreeMap<String, Geometry>) -> Result<()> {
+    let raw: BTreeMap<&str, String> = regions
+        .iter()
+        .map(|(name, g)| (name.as_str(), g.to_string()))
+        .collect();
+    write_state_file(REGIONS_FILE, &toml::to_string(&raw)?)
+}
+
+pub fn named_region(name: &str) -> Result<Geometry> {
+    let regions = load_regions()?;
+    match regions.get(name) {
+        Some(g) => Ok(*g),
+        None if regions.is_empty() => {
+            bail!("No region named ‘{name}’. Save one with `trein region save {name}`.")
+        }
+        None => bail!(
+            "No region named ‘{name}’ (saved: {})",
+            regions.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+/// Every corner of `g` must lie on some output, so a region saved on a
+/// monitor that is now unplugged or moved is caught before grim runs.
+pub fn check_fits(g: &Geometry, outputs: &[Geometry]) -> Result<()> {
+    if g.corners()
+        .iter()
+        .all(|&(x, y)| outputs.iter().any(|o| o.contains(x, y)))
+    {
+        return Ok(());
+    }
+    let outputs: Vec<String> = outputs.iter().map(Geometry::to_string).collect();
+    bail!(
+        "Region {g} is no longer on screen (outputs: {}). Select it again.",
+        outputs.join(", ")
+    )
+}
+
+/// Checks a stored region against the current outputs; skipped with a
+/// warning when the outputs can't be listed.
+pub async fn ensure_on_screen(g: &Geometry, capturer: &Capturer) -> Result<()> {
+    match capturer.outputs().await {
+        Ok(Some(outputs)) if !outputs.is_empty() => check_fits(g, &outputs),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            eprintln!("(Warning) not checking the region against the outputs: {e:#}");
+            Ok(())
+        }
+    }
+}
+
+pub async fn run_region(cmd: &RegionCommand, capture: &CaptureConfig) -> Result<()> {
+    match cmd {
+        RegionCommand::Save {
+            name,
+            geometry,
+            last,
+            select,
+        } => {
+            let g = match (geometry, last) {
+                (Some(g), _) => *g,
+                (None, true) => load_last_region()?,
+                (None, false) => Capturer::from_config(capture)?.select(*select).await?,
+            };
+            let mut regions = load_regions()?;
+            regions.insert(name.clone(), g);
+            save_regions(&regions)?;
+            println!("Saved region ‘{name}’: {g}");
+        }
+        RegionCommand::List => {
+            for (name, g) in load_regions()? {
+                println!("{name}\t{g}");
+            }
+        }
+        RegionCommand::Delete { name } => {
+            let mut regions = load_regions()?;
+            if regions.remove(name).is_none() {
+                bail!("No region named ‘{name}’");
+            }
+            save_regions(&regions)?;
+            println!("Deleted region ‘{name}’");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geometry_round_trips_slurp_format() {
+        let g: Geometry = "-1920,35 640x120".parse().unwrap();
+        assert_eq!(
+            g,
+            Geometry {
+                x: -1920,
+                y: 35,
+                width: 640,
+                height: 120
+            }
+        );
+        assert_eq!(g.to_string(), "-1920,35 640x120");
+        assert!("10,20".parse::<Geometry>().is_err());
+        assert!("10,20 0x5".parse::<Geometry>().is_err());
+        assert!("a,b 1x1".parse::<Geometry>().is_err());
+    }
+
+    #[test]
+    fn regions_file_round_trips() {
+        let regions =
+            parse_regions("dialog = \"10,20 640x120\"\nsubs = \"0,900 1920x180\"\n").unwrap();
+        assert_eq!(regions["dialog"].to_string(), "10,20 640x120");
+        assert!(parse_regions("bad = \"10,20\"").is_err());
+    }
+
+    #[test]
+    fn region_must_fit_the_outputs() {
+        let outputs = ["0,0 1920x1080", "1920,0 2560x1440"].map(|g| g.parse::<Geometry>().unwrap());
+        let fits = |g: &str| check_fits(&g.parse().unwrap(), &outputs);
+        assert!(fits("100,100 300x50").is_ok());
+        assert!(fits("1800,100 300x50").is_ok()); // spans both outputs
+        assert!(fits("1800,1000 300x200").is_err()); // bottom-left corner under DP-2 only
+        assert!(fits("-100,0 50x50").is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::{Context, Result, bail};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// `$XDG_STATE_HOME/trein`, falling back to `~/.local/state/trein`.
+pub fn state_dir() -> Result<PathBuf> {
+    if let Ok(xdg) = env::var("XDG_STATE_HOME")
+        && !xdg.trim().is_empty()
+    {
+        return Ok(PathBuf::from(xdg).join("trein"));
+    }
+    match env::var("HOME") {
+        Ok(home) => Ok(PathBuf::from(home).join(".local/state/trein")),
+        Err(_) => bail!("Neither $XDG_STATE_HOME nor $HOME is set"),
+    }
+}
+
+pub fn state_file(name: &str) -> Result<PathBuf> {
+    Ok(state_dir()?.join(name))
+}
+
+/// Writes `name` in the state dir, creating the directory if needed.
+pub fn write_state_file(name: &str, content: &str) -> Result<()> {
+    let path = state_file(name)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+    }
+    fs::write(&path, content).with_context(|| format!("Could not write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region::{Geometry, load_last_region, save_last_region};
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn last_region_is_kept_in_xdg_state_home() {
+        let dir = tempfile::tempdir().unwrap();
+        let saved = env::var_os("XDG_STATE_HOME");
+        unsafe { env::set_var("XDG_STATE_HOME", dir.path()) };
+
+        assert!(load_last_region().is_err());
+        let g: Geometry = "10,20 300x40".parse().unwrap();
+        save_last_region(&g).unwrap();
+        assert!(dir.path().join("trein/last-region").exists());
+        assert_eq!(load_last_region().unwrap(), g);
+
+        match saved {
+            Some(v) => unsafe { env::set_var("XDG_STATE_HOME", v) },
+            None => unsafe { env::remove_var("XDG_STATE_HOME") },
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use serde::Deserialize;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// `[timeouts]` in config.toml, in seconds; 0 disables a limit. Selecting a
+/// region waits for the user and has none.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Timeouts {
+    /// Compositor and display queries (hyprctl, swaymsg, xrandr, …).
+    pub query: u64,
+    pub capture: u64,
+    pub ocr: u64,
+    /// Each HTTP request to a translation backend, body included.
+    pub translate: u64,
+    pub clipboard: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            query: 5,
+            capture: 15,
+            ocr: 60,
+            translate: 60,
+            clipboard: 5,
+        }
+    }
+}
+
+static TIMEOUTS: OnceLock<Timeouts> = OnceLock::new();
+
+/// Installs the configured limits; call once at startup.
+pub fn init(timeouts: Timeouts) {
+    let _ = TIMEOUTS.set(timeouts);
+}
+
+/// A step of the pipeline, for time limits and error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Select,
+    Query,
+    Capture,
+    Ocr,
+    Translate,
+    Clipboard,
+}
+
+impl Stage {
+    pub fn limit(self) -> Option<Duration> {
+        let t = TIMEOUTS.get_or_init(Timeouts::default);
+        let secs = match self {
+            Stage::Select => return None,
+            Stage::Query => t.query,
+            Stage::Capture => t.capture,
+            Stage::Ocr => t.ocr,
+            Stage::Translate => t.translate,
+            Stage::Clipboard => t.clipboard,
+        };
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Stage::Select => "select",
+            Stage::Query => "query",
+            Stage::Capture => "capture",
+            Stage::Ocr => "ocr",
+            Stage::Translate => "translate",
+            Stage::Clipboard => "clipboard",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Select => "Selection",
+            Stage::Query => "Compositor query",
+            Stage::Capture => "Screen capture",
+            Stage::Ocr => "OCR",
+            Stage::Translate => "Translation",
+            Stage::Clipboard => "Clipboard copy",
+        })
+    }
+}
+
+/// A stage that ran past its limit.
+#[derive(Debug)]
+pub struct TimedOut {
+    pub stage: Stage,
+    pub after: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} timed out after {}s (raise `{}` under [timeouts] in config.toml)",
+            self.stage,
+            self.after.as_secs(),
+            self.stage.key()
+        )
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeouts_parse_and_report_the_stage() {
+        let t: Timeouts = toml::from_str("ocr = 120\nclipboard = 0").unwrap();
+        assert_eq!((t.ocr, t.clipboard, t.capture), (120, 0, 15));
+
+        let err = TimedOut {
+            stage: Stage::Ocr,
+            after: Duration::from_secs(60),
+        };
+        assert_eq!(
+            err.to_string(),
+            "OCR timed out after 60s (raise `ocr` under [timeouts] in config.toml)"
+        );
+        assert_eq!(Stage::Select.limit(), None);
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::{Output, Stdio};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::timeouts::{Stage, TimedOut};
+
+/// `[tools.NAME]` in config.toml: run `path` instead of NAME from PATH and
+/// pass `args` before trein's own, e.g. `path = "flatpak-spawn"`,
+/// `args = ["--host", "grim", "-s", "2"]`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ToolConfig {
+    pub path: Option<String>,
+    pub args: Vec<String>,
+}
+
+static TOOLS: OnceLock<BTreeMap<String, ToolConfig>> = OnceLock::new();
+
+/// Installs the configured tools; call once, before running any of them.
+pub fn init(tools: BTreeMap<String, ToolConfig>) {
+    let _ = TOOLS.set(tools);
+}
+
+/// A command for the external tool `name` (slurp, grim, tesseract, wl-copy,
+/// …) with its configured path and extra arguments. Its output is captured
+/// and it is killed if dropped (on timeout or Ctrl-C).
+pub fn command(name: &str) -> Command {
+    let (program, args) = resolve(TOOLS.get(), name);
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    command
+}
+
+/// Runs `command` to completion, feeding it `input` on stdin; past the
+/// stage's time limit the process is killed.
+pub async fn run(
+    command: Command,
+    name: &str,
+    input: Option<&[u8]>,
+    stage: Stage,
+) -> Result<Output> {
+    run_with_limit(command, name, input, stage, stage.limit()).await
+}
+
+async fn run_with_limit(
+    mut command: Command,
+    name: &str,
+    input: Option<&[u8]>,
+    stage: Stage,
+    limit: Option<Duration>,
+) -> Result<Output> {
+    command.stdin(if input.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    });
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to run `{name}` (is it installed?)"))?;
+    let stdin = child.stdin.take();
+    let work = async move {
+        if let (Some(input), Some(mut stdin)) = (input, stdin) {
+            stdin.write_all(input).await?;
+        }
+        child.wait_with_output().await
+    };
+    let out = match limit {
+        Some(after) => tokio::time::timeout(after, work).await.map_err(|_| {
+            anyhow::Error::new(TimedOut { stage, after })
+                .context(format!("`{name}` did not finish and was stopped"))
+        })?,
+        None => work.await,
+    };
+    out.with_context(|| format!("Failed to run `{name}`"))
+}
+
+/// Whether `name` can be run: a configured path that exists, or found on PATH.
+pub fn available(name: &str) -> bool {
+    let (program, _) = resolve(TOOLS.get(), name);
+    if program.contains('/') {
+        return Path::new(&program).is_file();
+    }
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(&program).is_file()))
+}
+
+fn resolve(tools: Option<&BTreeMap<String, ToolConfig>>, name: &str) -> (String, Vec<String>) {
+    match tools.and_then(|t| t.get(name)) {
+        Some(tool) => (
+            tool.path.clone().unwrap_or_else(|| name.to_string()),
+            tool.args.clone(),
+        ),
+        None => (name.to_string(), Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_path_and_args_come_first() {
+        let tools: BTreeMap<String, ToolConfig> = toml::from_str(
+            r##"
+            slurp = { args = ["-b", "#00000080"] }
+            grim = { path = "/nix/store/abc-grim/bin/grim", args = ["-s", "2"] }
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            resolve(Some(&tools), "slurp"),
+            ("slurp".into(), vec!["-b".into(), "#00000080".into()])
+        );
+        assert_eq!(
+            resolve(Some(&tools), "grim"),
+            (
+                "/nix/store/abc-grim/bin/grim".into(),
+                vec!["-s".into(), "2".into()]
+            )
+        );
+        assert_eq!(resolve(Some(&tools), "wl-copy"), ("wl-copy".into(), vec![]));
+        assert_eq!(resolve(None, "tesseract"), ("tesseract".into(), vec![]));
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn slow_tools_are_stopped_and_named() {
+        let mut sleep = command("sleep");
+        sleep.arg("5");
+        let start = std::time::Instant::now();
+        let limit = Some(Duration::from_millis(100));
+        let err = run_with_limit(sleep, "sleep", None, Stage::Ocr, limit)
+            .await
+            .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(err.is::<TimedOut>());
+        assert!(format!("{err:#}").contains("`sleep` did not finish"));
+
+        let mut cat = command("cat");
+        cat.stdout(Stdio::piped());
+        let out = run_with_limit(cat, "cat", Some(b"hi"), Stage::Ocr, limit)
+            .await
+            .unwrap();
+        assert_eq!(out.stdout, b"hi");
+    }
+}
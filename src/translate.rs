@@ -1,5 +1,111 @@
-use anyhow::{Context, Result, anyhow};
-use serde::Deserialize;
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use reqwest::header::AUTHORIZATION;
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+use crate::backtranslate::BackTranslation;
+use crate::http::{RetryPolicy, StatusError, redact, send_with_retry};
+
+/// Connection details for one DeepL account.
+#[derive(Clone)]
+pub struct DeeplApi {
+    pub client: reqwest::Client,
+    pub retry: RetryPolicy,
+    pub api_key: String,
+    pub base_url: String,
+}
+
+impl DeeplApi {
+    pub fn auth(&self) -> String {
+        format!("DeepL-Auth-Key {}", self.api_key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Formality {
+    Default,
+    More,
+    Less,
+    PreferMore,
+    PreferLess,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum SplitSentences {
+    #[serde(rename = "0", alias = "off")]
+    Off,
+    #[serde(rename = "1", alias = "on")]
+    On,
+    #[serde(rename = "nonewlines")]
+    Nonewlines,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::enum_variant_names)] // mirrors DeepL's parameter values
+pub enum ModelType {
+    QualityOptimized,
+    PreferQualityOptimized,
+    LatencyOptimized,
+}
+
+/// Optional `/v2/translate` parameters; unset fields are left to DeepL's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeeplOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formality: Option<Formality>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub split_sentences: Option<SplitSentences>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preserve_formatting: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_type: Option<ModelType>,
+    /// Resolved from a glossary name at runtime, never read from config.
+    #[serde(skip_serializing_if = "Option::is_none", skip_deserializing)]
+    pub glossary_id: Option<String>,
+}
+
+impl DeeplOptions {
+    /// Fields set in `over` win over the ones in `self`.
+    pub fn merged(self, over: DeeplOptions) -> DeeplOptions {
+        DeeplOptions {
+            formality: over.formality.or(self.formality),
+            context: over.context.or(self.context),
+            split_sentences: over.split_sentences.or(self.split_sentences),
+            preserve_formatting: over.preserve_formatting.or(self.preserve_formatting),
+            model_type: over.model_type.or(self.model_type),
+            glossary_id: over.glossary_id.or(self.glossary_id),
+        }
+    }
+}
+
+/// One finished translation, as rendered and copied.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Translation {
+    pub target: String,
+    pub text: String,
+    pub detected_source: Option<String>,
+    /// Which backend served it, when several were configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub back: Option<BackTranslation>,
+}
+
+#[derive(Serialize)]
+struct DeeplRequest<'a> {
+    text: Vec<&'a str>,
+    target_lang: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_lang: Option<&'a str>,
+    #[serde(flatten)]
+    options: &'a DeeplOptions,
+}
 
 #[derive(Deserialize)]
 struct DeeplTranslation {
@@ -13,45 +119,144 @@ struct DeeplResponse {
     translations: Vec<DeeplTranslation>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DeeplLanguage {
+    pub language: String,
+    #[serde(default)]
+    pub supports_formality: bool,
+}
+
 pub async fn translate_deepl(
-    client: &reqwest::Client,
-    api_key: &str,
-    base_url: &str,
+    api: &DeeplApi,
     text: &str,
     target: &str,
     source_opt: Option<&str>,
+    options: &DeeplOptions,
 ) -> Result<(String, Option<String>)> {
-    let url = format!("{}/v2/translate", base_url);
-
-    // form fields
-    let mut form: Vec<(String, String)> = vec![
-        ("auth_key".into(), api_key.to_string()),
-        ("text".into(), text.to_string()),
-        ("target_lang".into(), target.to_string()),
-    ];
-    if let Some(src) = source_opt {
-        form.push(("source_lang".into(), src.to_string()));
-    }
-
-    let resp = client
-        .post(url)
-        .form(&form)
-        .send()
-        .await
-        .context("Failed to contact DeepL")?
-        .error_for_status()
-        .context("DeepL returned an error status")?;
+    let mut results = translate_deepl_many(api, &[text], target, source_opt, options).await?;
+    Ok(results.remove(0))
+}
+
+/// Translates several texts in one request (one `text` parameter each);
+/// results come back in the same order.
+pub async fn translate_deepl_many(
+    api: &DeeplApi,
+    texts: &[&str],
+    target: &str,
+    source_opt: Option<&str>,
+    options: &DeeplOptions,
+) -> Result<Vec<(String, Option<String>)>> {
+    let url = format!("{}/v2/translate", api.base_url);
+
+    let body = DeeplRequest {
+        text: texts.to_vec(),
+        target_lang: target,
+        source_lang: source_opt,
+        options,
+    };
+    let auth = api.auth();
+
+    let resp = send_with_retry(&api.retry, || {
+        api.client
+            .post(&url)
+            .header(AUTHORIZATION, &auth)
+            .json(&body)
+    })
+    .await
+    .map_err(|e| deepl_error(e, &api.api_key))?;
 
     let parsed: DeeplResponse = resp.json().await.context("Invalid JSON from DeepL")?;
-    let first = parsed
+    if parsed.translations.len() != texts.len() {
+        bail!(
+            "DeepL returned {} translation(s) for {} text(s)",
+            parsed.translations.len(),
+            texts.len()
+        );
+    }
+    Ok(parsed
         .translations
-        .get(0)
-        .ok_or_else(|| anyhow!("No translation in response"))?;
+        .into_iter()
+        .map(|t| (t.text.trim().to_string(), t.detected_source_language))
+        .collect())
+}
 
-    Ok((
-        first.text.trim().to_string(),
-        first.detected_source_language.clone(),
-    ))
+/// `GET /v2/languages`; `kind` is "source" or "target".
+pub async fn deepl_languages(api: &DeeplApi, kind: &str) -> Result<Vec<DeeplLanguage>> {
+    let url = format!("{}/v2/languages", api.base_url);
+    let auth = api.auth();
+    let resp = send_with_retry(&api.retry, || {
+        api.client
+            .get(&url)
+            .query(&[("type", kind)])
+            .header(AUTHORIZATION, &auth)
+    })
+    .await
+    .map_err(|e| deepl_error(e, &api.api_key))?;
+    resp.json()
+        .await
+        .context("Invalid JSON from DeepL /v2/languages")
+}
+
+/// Checks `options` against what DeepL reports for `target`. Only strict
+/// formality needs this: the `prefer_*` variants degrade gracefully. The
+/// target list is fetched into `languages` on first use.
+pub async fn validate_deepl_options(
+    api: &DeeplApi,
+    languages: &OnceCell<Vec<DeeplLanguage>>,
+    target: &str,
+    options: &DeeplOptions,
+) -> Result<()> {
+    if !matches!(options.formality, Some(Formality::More | Formality::Less)) {
+        return Ok(());
+    }
+    let languages = languages
+        .get_or_try_init(|| deepl_languages(api, "target"))
+        .await?;
+    check_formality(languages, target)
+}
+
+// DeepL lists only the regional variants of some targets (EN-GB/EN-US,
+// PT-BR/PT-PT) but still accepts the bare code; it supports formality when
+// every variant does.
+fn check_formality(languages: &[DeeplLanguage], target: &str) -> Result<()> {
+    let variant = format!("{}-", target.to_ascii_uppercase());
+    let matching: Vec<&DeeplLanguage> = match languages
+        .iter()
+        .find(|l| l.language.eq_ignore_ascii_case(target))
+    {
+        Some(lang) => vec![lang],
+        None => languages
+            .iter()
+            .filter(|l| l.language.to_ascii_uppercase().starts_with(&variant))
+            .collect(),
+    };
+    if matching.is_empty() {
+        bail!("DeepL does not list {target} as a target language");
+    }
+    if !matching.iter().all(|l| l.supports_formality) {
+        bail!(
+            "DeepL does not support formality for {target}. \
+             Use prefer_more/prefer_less to fall back silently."
+        );
+    }
+    Ok(())
+}
+
+// Attach a human hint for the DeepL statuses people actually run into, and
+// make sure the key never ends up in an error message.
+pub fn deepl_error(mut err: anyhow::Error, api_key: &str) -> anyhow::Error {
+    let Some(status_err) = err.downcast_mut::<StatusError>() else {
+        return err.context("Failed to contact DeepL");
+    };
+    status_err.body = redact(&status_err.body, api_key);
+    match status_err.status.as_u16() {
+        403 => {
+            err.context("DeepL rejected the API key (403). Check the key and Free/Pro endpoint.")
+        }
+        456 => err.context("DeepL character quota exceeded (456)"),
+        429 => err.context("DeepL kept rate-limiting the request (429)"),
+        _ => err.context("DeepL returned an error status"),
+    }
 }
 
 pub fn deepl_source(code: &str) -> Result<String> {
@@ -80,6 +285,13 @@ pub fn deepl_source(code: &str) -> Result<String> {
     }
 }
 
+/// The source code matching a target code, e.g. EN-GB → EN, ZH-HANT → ZH.
+pub fn deepl_source_for_target(code: &str) -> Result<String> {
+    let target = deepl_target(code)?;
+    let base = target.split('-').next().unwrap_or(&target);
+    deepl_source(base)
+}
+
 pub fn deepl_target(code: &str) -> Result<String> {
     use anyhow::anyhow;
     use std::fmt::Write;
@@ -107,6 +319,15 @@ mod tests {
     use httpmock::prelude::*;
     use serde_json::json;
 
+    fn test_api(key: &str, server: &MockServer) -> DeeplApi {
+        DeeplApi {
+            client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
+            api_key: key.to_string(),
+            base_url: server.base_url(),
+        }
+    }
+
     #[test]
     fn deepl_code_normalization_and_validation() {
         // source
@@ -119,6 +340,11 @@ mod tests {
         assert_eq!(deepl_target("pl").unwrap(), "PL");
         assert_eq!(deepl_target("zh-hant").unwrap(), "ZH-HANT");
         assert!(deepl_target("xx").is_err());
+
+        // target → source variant
+        assert_eq!(deepl_source_for_target("en-gb").unwrap(), "EN");
+        assert_eq!(deepl_source_for_target("ES-419").unwrap(), "ES");
+        assert_eq!(deepl_source_for_target("ZH-HANT").unwrap(), "ZH");
     }
 
     #[tokio::test]
@@ -126,7 +352,14 @@ mod tests {
         let server = MockServer::start();
 
         let m = server.mock(|when, then| {
-            when.method(POST).path("/v2/translate");
+            when.method(POST)
+                .path("/v2/translate")
+                .header("Authorization", "DeepL-Auth-Key dummy-key")
+                .json_body(json!({
+                    "text": ["Hello"],
+                    "target_lang": "FR",
+                    "source_lang": "EN"
+                }));
             then.status(200).json_body(json!({
                 "translations": [{
                     "text": "Bonjour",
@@ -135,20 +368,137 @@ mod tests {
             }));
         });
 
-        let client = reqwest::Client::new();
-        let (text, detected) = super::translate_deepl(
-            &client,
-            "dummy-key",
-            &server.base_url(),
-            "Hello",
-            "FR",
+        let api = test_api("dummy-key", &server);
+        let (text, detected) =
+            super::translate_deepl(&api, "Hello", "FR", Some("EN"), &DeeplOptions::default())
+                .await
+                .unwrap();
+
+        assert_eq!(text, "Bonjour");
+        assert_eq!(detected.as_deref(), Some("EN"));
+        m.assert();
+    }
+
+    #[tokio::test]
+    async fn translate_deepl_many_sends_one_request() {
+        let server = MockServer::start();
+        let m = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .json_body_partial(r#"{"text":["one","two"],"target_lang":"DE"}"#);
+            then.status(200).json_body(json!({
+                "translations": [{"text": "eins"}, {"text": " zwei "}]
+            }));
+        });
+
+        let api = test_api("k", &server);
+        let out = translate_deepl_many(
+            &api,
+            &["one", "two"],
+            "DE",
             Some("EN"),
+            &DeeplOptions::default(),
         )
         .await
         .unwrap();
+        assert_eq!(out, vec![("eins".into(), None), ("zwei".into(), None)]);
+        m.assert_hits(1);
+    }
 
-        assert_eq!(text, "Bonjour");
-        assert_eq!(detected.as_deref(), Some("EN"));
+    #[tokio::test]
+    async fn translate_deepl_does_not_retry_forbidden() {
+        let server = MockServer::start();
+        let m = server.mock(|when, then| {
+            when.method(POST).path("/v2/translate");
+            then.status(403).body("Forbidden: bad-key");
+        });
+
+        let api = test_api("bad-key", &server);
+        let err = super::translate_deepl(&api, "Hello", "FR", Some("EN"), &DeeplOptions::default())
+            .await
+            .unwrap_err();
+
+        m.assert_hits(1);
+        let msg = format!("{err:#}");
+        assert!(msg.contains("rejected the API key"));
+        assert!(!msg.contains("bad-key"), "key leaked: {msg}");
+    }
+
+    #[tokio::test]
+    async fn translate_deepl_sends_options() {
+        let server = MockServer::start();
+        let m = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v2/translate")
+                .json_body_partial(
+                    r#"{"formality":"prefer_more","split_sentences":"nonewlines","preserve_formatting":true,"model_type":"quality_optimized","context":"UI label"}"#,
+                );
+            then.status(200)
+                .json_body(json!({"translations": [{"text": "Sie"}]}));
+        });
+
+        let options = DeeplOptions {
+            formality: Some(Formality::PreferMore),
+            context: Some("UI label".into()),
+            split_sentences: Some(SplitSentences::Nonewlines),
+            preserve_formatting: Some(true),
+            model_type: Some(ModelType::QualityOptimized),
+            glossary_id: None,
+        };
+        let api = test_api("k", &server);
+        let (text, _) = translate_deepl(&api, "you", "DE", Some("EN"), &options)
+            .await
+            .unwrap();
+        assert_eq!(text, "Sie");
         m.assert();
     }
+
+    #[test]
+    fn formality_is_checked_against_reported_languages() {
+        let languages = vec![
+            DeeplLanguage {
+                language: "DE".into(),
+                supports_formality: true,
+            },
+            DeeplLanguage {
+                language: "EN-GB".into(),
+                supports_formality: false,
+            },
+        ];
+        assert!(check_formality(&languages, "DE").is_ok());
+        assert!(check_formality(&languages, "EN-GB").is_err());
+        assert!(check_formality(&languages, "XX").is_err());
+    }
+
+    #[test]
+    fn formality_for_bare_codes_follows_their_variants() {
+        let languages: Vec<DeeplLanguage> = serde_json::from_value(json!([
+            {"language": "EN-GB", "supports_formality": false},
+            {"language": "EN-US", "supports_formality": false},
+            {"language": "PT-BR", "supports_formality": true},
+            {"language": "PT-PT", "supports_formality": true}
+        ]))
+        .unwrap();
+        assert!(check_formality(&languages, "PT").is_ok());
+        let err = check_formality(&languages, "EN").unwrap_err().to_string();
+        assert!(err.contains("does not support formality for EN"), "{err}");
+        let err = check_formality(&languages, "ZH").unwrap_err().to_string();
+        assert!(err.contains("does not list ZH"), "{err}");
+    }
+
+    #[test]
+    fn options_merge_prefers_overrides() {
+        let base = DeeplOptions {
+            formality: Some(Formality::Less),
+            preserve_formatting: Some(true),
+            ..Default::default()
+        };
+        let over = DeeplOptions {
+            formality: Some(Formality::More),
+            ..Default::default()
+        };
+        let merged = base.merged(over);
+        assert_eq!(merged.formality, Some(Formality::More));
+        assert_eq!(merged.preserve_formatting, Some(true));
+    }
 }
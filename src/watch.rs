@@ -0,0 +1,225 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::backtranslate::similarity;
+use crate::capture::Capturer;
+use crate::ocr::ocr_image;
+use crate::region::Geometry;
+
+/// Frames whose hashes differ in at most this many bits count as the same.
+const SAME_IMAGE_BITS: u32 = 3;
+/// OCR results at least this similar count as the same text.
+const SAME_TEXT: f64 = 0.9;
+
+pub struct WatchOptions {
+    pub interval: Duration,
+    /// Polls the text must stay the same before it is translated.
+    pub stable_polls: u32,
+}
+
+/// Decides when a watched region shows new text worth translating: the
+/// image must have changed, and the new text must survive `stable_polls`
+/// captures so fades and flicker don't trigger translations.
+pub struct Watcher {
+    stable_polls: u32,
+    /// Hash of the last frame that was OCR'd.
+    ocr_hash: Option<u64>,
+    candidate: Option<(String, u32)>,
+    shown: Option<String>,
+}
+
+impl Watcher {
+    pub fn new(stable_polls: u32) -> Self {
+        Self {
+            stable_polls: stable_polls.max(1),
+            ocr_hash: None,
+            candidate: None,
+            shown: None,
+        }
+    }
+
+    /// Whether this frame looks like the last one OCR'd, so OCR can be
+    /// skipped. Otherwise the frame becomes the new reference, so a slow
+    /// fade or scroll still adds up to a change.
+    pub fn same_image(&mut self, hash: u64) -> bool {
+        let same = self
+            .ocr_hash
+            .is_some_and(|h| (h ^ hash).count_ones() <= SAME_IMAGE_BITS);
+        if !same {
+            self.ocr_hash = Some(hash);
+        }
+        same
+    }
+
+    /// Feeds one OCR result; returns the text once it is stable and differs
+    /// from what was last shown.
+    pub fn observe(&mut self, text: &str) -> Option<String> {
+        let text = text.trim();
+        if text.is_empty() {
+            self.candidate = None;
+            return None;
+        }
+        let seen = match &self.candidate {
+            Some((c, n)) if similarity(c, text) >= SAME_TEXT => n + 1,
+            _ => 1,
+        };
+        self.candidate = Some((text.to_string(), seen));
+        if seen < self.stable_polls
+            || self
+                .shown
+                .as_deref()
+                .is_some_and(|s| similarity(s, text) >= SAME_TEXT)
+        {
+            return None;
+        }
+        self.shown = Some(text.to_string());
+        Some(text.to_string())
+    }
+}
+
+/// 64-bit difference hash of a PNG: a 9×8 grayscale thumbnail, one bit per
+/// horizontally adjacent pair. Small rendering noise leaves it unchanged.
+pub fn dhash(png_path: &Path) -> Result<u64> {
+    let file =
+        File::open(png_path).with_context(|| format!("Could not open {}", png_path.display()))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().context("Invalid PNG")?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).context("Invalid PNG")?;
+    let (width, height) = (info.width as usize, info.height as usize);
+    let channels = info.color_type.samples();
+
+    let mut sums = [[0u64; 9]; 8];
+    let mut counts = [[0u64; 9]; 8];
+    for y in 0..height {
+        let row = &buf[y * info.line_size..][..width * channels];
+        for (x, px) in row.chunks_exact(channels).enumerate() {
+            let gray = match channels {
+                1 | 2 => px[0] as u64,
+                _ => (px[0] as u64 * 299 + px[1] as u64 * 587 + px[2] as u64 * 114) / 1000,
+            };
+            let (cy, cx) = (y * 8 / height, x * 9 / width);
+            sums[cy][cx] += gray;
+            counts[cy][cx] += 1;
+        }
+    }
+    let mut hash = 0u64;
+    for (s, c) in sums.iter().zip(&counts) {
+        let cell = |i: usize| s[i].checked_div(c[i]).unwrap_or(0);
+        for i in 0..8 {
+            hash = (hash << 1) | (cell(i) < cell(i + 1)) as u64;
+        }
+    }
+    Ok(hash)
+}
+
+/// Re-captures `geometry` every `interval` and calls `on_change` with new
+/// text until Ctrl-C. Translation errors are reported without stopping.
+pub async fn run_watch(
+    capturer: &Capturer,
+    geometry: &Geometry,
+    ocr_pack: &str,
+    opts: &WatchOptions,
+    mut on_change: impl AsyncFnMut(String) -> Result<()>,
+) -> Result<()> {
+    let mut watcher = Watcher::new(opts.stable_polls);
+    let mut text = String::new();
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    eprintln!(
+        "Watching {geometry} every {:?}; press Ctrl-C to stop.",
+        opts.interval
+    );
+    loop {
+        let step = async {
+            let (tmpdir, png_path) = capturer.capture(geometry).await?;
+            // A frame we can't hash is simply OCR'd.
+            let same = dhash(&png_path).is_ok_and(|h| watcher.same_image(h));
+            if !same {
+                text = ocr_image(&png_path, ocr_pack, None).await?.text;
+            }
+            drop(tmpdir);
+            if let Some(new) = watcher.observe(&text)
+                && let Err(e) = on_change(new).await
+            {
+                eprintln!("(Watch) {e:#}");
+            }
+            tokio::time::sleep(opts.interval).await;
+            anyhow::Ok(())
+        };
+        tokio::select! {
+            r = step => r?,
+            _ = &mut ctrl_c => {
+                eprintln!("Stopped watching.");
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_png(path: &Path, width: u32, height: u32, pixel: impl Fn(u32, u32) -> u8) {
+        let file = File::create(path).unwrap();
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        let mut writer = encoder.write_header().unwrap();
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                data.extend([pixel(x, y); 3]);
+            }
+        }
+        writer.write_image_data(&data).unwrap();
+    }
+
+    #[test]
+    fn dhash_ignores_noise_but_not_new_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, c) = (
+            dir.path().join("a.png"),
+            dir.path().join("b.png"),
+            dir.path().join("c.png"),
+        );
+        write_png(&a, 90, 40, |x, y| ((x * 7 + y * 3) % 200) as u8);
+        write_png(&b, 90, 40, |x, y| {
+            ((x * 7 + y * 3) % 200) as u8 + (x == 5) as u8
+        });
+        write_png(&c, 90, 40, |x, _| if x < 45 { 250 } else { 10 });
+
+        let mut watcher = Watcher::new(1);
+        assert!(!watcher.same_image(dhash(&a).unwrap()));
+        assert!(watcher.same_image(dhash(&b).unwrap()));
+        assert!(!watcher.same_image(dhash(&c).unwrap()));
+    }
+
+    #[test]
+    fn slow_changes_add_up() {
+        let mut watcher = Watcher::new(1);
+        assert!(!watcher.same_image(0));
+        assert!(watcher.same_image(0b11));
+        // Two bits from the previous frame, but four from the one OCR'd.
+        assert!(!watcher.same_image(0b1111));
+        assert!(watcher.same_image(0b1111));
+    }
+
+    #[test]
+    fn text_is_debounced_and_not_repeated() {
+        let mut watcher = Watcher::new(2);
+        assert_eq!(watcher.observe("Hello there"), None); // first sighting
+        assert_eq!(
+            watcher.observe("Hello there!").as_deref(),
+            Some("Hello there!")
+        );
+        assert_eq!(watcher.observe("Hello there"), None); // already shown
+        assert_eq!(watcher.observe("Goodbye"), None); // flicker…
+        assert_eq!(watcher.observe(""), None); // …gone again
+        assert_eq!(watcher.observe("Goodbye"), None);
+        assert_eq!(watcher.observe("Goodbye").as_deref(), Some("Goodbye"));
+    }
+}
@@ -1,8 +1,367 @@
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
 
-pub fn require_wayland() -> Result<()> {
-    if std::env::var("WAYLAND_DISPLAY").is_err() {
-        bail!("This tool must run under Wayland (Hyprland). $WAYLAND_DISPLAY is not set.");
+use crate::region::Geometry;
+use crate::timeouts::Stage;
+use crate::tools;
+
+/// The running compositor, as far as window and output queries go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compositor {
+    Hyprland,
+    Sway,
+    /// GNOME and KDE don't implement the wlroots screencopy protocol grim uses.
+    Gnome,
+    Kde,
+    /// Any other wlroots compositor: only slurp and wlr-randr are available.
+    Other,
+}
+
+impl Compositor {
+    pub fn detect() -> Self {
+        let var = |k: &str| std::env::var(k).is_ok_and(|v| !v.is_empty());
+        let desktop = std::env::var("XDG_CURRENT_DESKTOP")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if var("HYPRLAND_INSTANCE_SIGNATURE") || desktop.contains("hyprland") {
+            Compositor::Hyprland
+        } else if var("SWAYSOCK") || desktop.contains("sway") {
+            Compositor::Sway
+        } else if desktop.contains("gnome") {
+            Compositor::Gnome
+        } else if desktop.contains("kde") {
+            Compositor::Kde
+        } else {
+            Compositor::Other
+        }
+    }
+
+    /// Whether grim and slurp work here.
+    pub fn is_wlroots(self) -> bool {
+        !matches!(self, Compositor::Gnome | Compositor::Kde)
+    }
+}
+
+/// The graphical session trein is running in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Session {
+    Wayland(Compositor),
+    X11,
+    None,
+}
+
+impl Session {
+    pub fn detect() -> Self {
+        let var = |k: &str| std::env::var(k).is_ok_and(|v| !v.is_empty());
+        if var("WAYLAND_DISPLAY") {
+            Session::Wayland(Compositor::detect())
+        } else if var("DISPLAY") {
+            Session::X11
+        } else {
+            Session::None
+        }
+    }
+}
+
+/// Logical areas of the enabled outputs.
+pub async fn current_outputs() -> Result<Vec<Geometry>> {
+    Ok(outputs(Compositor::detect())
+        .await?
+        .into_iter()
+        .map(|(g, _)| g)
+        .collect())
+}
+
+/// The focused window's area.
+pub async fn focused_window(compositor: Compositor) -> Result<Geometry> {
+    match compositor {
+        Compositor::Hyprland => {
+            hyprland_window(&run_json("hyprctl", &["-j", "activewindow"]).await?)
+        }
+        Compositor::Sway => sway_windows(&run_json("swaymsg", &["-t", "get_tree", "-r"]).await?)
+            .into_iter()
+            .find_map(|(g, focused)| focused.then_some(g))
+            .context("No focused window"),
+        _ => bail!("Window capture needs Hyprland or Sway; use --select region"),
+    }
+}
+
+/// Areas of the windows currently on screen.
+pub async fn visible_windows(compositor: Compositor) -> Result<Vec<Geometry>> {
+    match compositor {
+        Compositor::Hyprland => hyprland_clients(
+            &run_json("hyprctl", &["-j", "clients"]).await?,
+            &run_json("hyprctl", &["-j", "monitors"]).await?,
+        ),
+        Compositor::Sway => Ok(sway_windows(
+            &run_json("swaymsg", &["-t", "get_tree", "-r"]).await?,
+        )
+        .into_iter()
+        .map(|(g, _)| g)
+        .collect()),
+        _ => bail!("Window capture needs Hyprland or Sway; use --select region"),
+    }
+}
+
+/// The focused output's area, or `None` if the compositor can't tell.
+pub async fn focused_output(compositor: Compositor) -> Result<Option<Geometry>> {
+    Ok(outputs(compositor)
+        .await?
+        .into_iter()
+        .find_map(|(g, focused)| focused.then_some(g)))
+}
+
+/// (area, focused) for each enabled output.
+async fn outputs(compositor: Compositor) -> Result<Vec<(Geometry, bool)>> {
+    match compositor {
+        Compositor::Hyprland => hyprland_outputs(&run_json("hyprctl", &["monitors", "-j"]).await?),
+        Compositor::Sway => sway_outputs(&run_json("swaymsg", &["-t", "get_outputs", "-r"]).await?),
+        Compositor::Other => {
+            let json = run_json("wlr-randr", &["--json"])
+                .await
+                .context("Could not list outputs with `wlr-randr`")?;
+            wlr_randr_outputs(&json)
+        }
+        Compositor::Gnome | Compositor::Kde => bail!("Can't list outputs on {compositor:?}"),
+    }
+}
+
+async fn run_json(program: &str, args: &[&str]) -> Result<Value> {
+    let mut command = tools::command(program);
+    command.args(args);
+    let out = tools::run(command, program, None, Stage::Query).await?;
+    if !out.status.success() {
+        bail!("`{program}` failed");
+    }
+    serde_json::from_slice(&out.stdout).with_context(|| format!("Invalid JSON from `{program}`"))
+}
+
+/// Pixel mode → logical size: divided by the scale, swapped when rotated.
+fn logical(x: i64, y: i64, width: f64, height: f64, scale: f64, rotated: bool) -> Geometry {
+    let scale = if scale > 0.0 { scale } else { 1.0 };
+    let (w, h) = (width / scale, height / scale);
+    let (w, h) = if rotated { (h, w) } else { (w, h) };
+    Geometry {
+        x: x as i32,
+        y: y as i32,
+        width: w.round() as u32,
+        height: h.round() as u32,
+    }
+}
+
+/// `{"x", "y", "width", "height"}` as used by Sway.
+fn rect(v: &Value) -> Option<Geometry> {
+    Some(Geometry {
+        x: v["x"].as_i64()? as i32,
+        y: v["y"].as_i64()? as i32,
+        width: v["width"].as_u64()? as u32,
+        height: v["height"].as_u64()? as u32,
+    })
+}
+
+/// `"at": [x, y], "size": [w, h]` as used by Hyprland.
+fn at_size(v: &Value) -> Option<Geometry> {
+    Some(Geometry {
+        x: v["at"][0].as_i64()? as i32,
+        y: v["at"][1].as_i64()? as i32,
+        width: v["size"][0].as_u64()? as u32,
+        height: v["size"][1].as_u64()? as u32,
+    })
+}
+
+fn hyprland_outputs(json: &Value) -> Result<Vec<(Geometry, bool)>> {
+    let monitors = json
+        .as_array()
+        .context("Unexpected `hyprctl monitors -j` output")?;
+    Ok(monitors
+        .iter()
+        .filter(|m| !m["disabled"].as_bool().unwrap_or(false))
+        .map(|m| {
+            let g = logical(
+                m["x"].as_i64().unwrap_or(0),
+                m["y"].as_i64().unwrap_or(0),
+                m["width"].as_f64().unwrap_or(0.0),
+                m["height"].as_f64().unwrap_or(0.0),
+                m["scale"].as_f64().unwrap_or(1.0),
+                m["transform"].as_i64().unwrap_or(0) % 2 == 1,
+            );
+            (g, m["focused"].as_bool().unwrap_or(false))
+        })
+        .collect())
+}
+
+fn hyprland_window(json: &Value) -> Result<Geometry> {
+    at_size(json).context("No focused window")
+}
+
+/// Mapped, unhidden clients on the workspaces the monitors are showing.
+fn hyprland_clients(clients: &Value, monitors: &Value) -> Result<Vec<Geometry>> {
+    let shown: Vec<i64> = monitors
+        .as_array()
+        .context("Unexpected `hyprctl -j monitors` output")?
+        .iter()
+        .filter_map(|m| m["activeWorkspace"]["id"].as_i64())
+        .collect();
+    Ok(clients
+        .as_array()
+        .context("Unexpected `hyprctl -j clients` output")?
+        .iter()
+        .filter(|c| {
+            c["mapped"].as_bool().unwrap_or(true) && !c["hidden"].as_bool().unwrap_or(false)
+        })
+        .filter(|c| {
+            c["workspace"]["id"]
+                .as_i64()
+                .is_some_and(|id| shown.contains(&id))
+        })
+        .filter_map(at_size)
+        .collect())
+}
+
+/// (area, focused) of every visible application window in a Sway tree.
+fn sway_windows(tree: &Value) -> Vec<(Geometry, bool)> {
+    let mut out = Vec::new();
+    let mut stack = vec![tree];
+    while let Some(node) = stack.pop() {
+        let is_window = !node["pid"].is_null() && node["visible"].as_bool().unwrap_or(false);
+        if is_window && let Some(g) = rect(&node["rect"]) {
+            out.push((g, node["focused"].as_bool().unwrap_or(false)));
+        }
+        for key in ["nodes", "floating_nodes"] {
+            if let Some(children) = node[key].as_array() {
+                stack.extend(children);
+            }
+        }
+    }
+    out
+}
+
+fn sway_outputs(json: &Value) -> Result<Vec<(Geometry, bool)>> {
+    let outputs = json
+        .as_array()
+        .context("Unexpected `swaymsg -t get_outputs` output")?;
+    Ok(outputs
+        .iter()
+        .filter(|o| o["active"].as_bool().unwrap_or(true))
+        .filter_map(|o| Some((rect(&o["rect"])?, o["focused"].as_bool().unwrap_or(false))))
+        .collect())
+}
+
+fn wlr_randr_outputs(json: &Value) -> Result<Vec<(Geometry, bool)>> {
+    let outputs = json
+        .as_array()
+        .context("Unexpected `wlr-randr --json` output")?;
+    Ok(outputs
+        .iter()
+        .filter(|o| o["enabled"].as_bool().unwrap_or(true))
+        .filter_map(|o| {
+            let mode = o["modes"]
+                .as_array()?
+                .iter()
+                .find(|m| m["current"].as_bool().unwrap_or(false))?;
+            let transform = o["transform"].as_str().unwrap_or("normal");
+            let g = logical(
+                o["position"]["x"].as_i64().unwrap_or(0),
+                o["position"]["y"].as_i64().unwrap_or(0),
+                mode["width"].as_f64()?,
+                mode["height"].as_f64()?,
+                o["scale"].as_f64().unwrap_or(1.0),
+                transform.ends_with("90") || transform.ends_with("270"),
+            );
+            Some((g, false))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn geo(s: &str) -> Geometry {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn outputs_are_in_logical_coordinates() {
+        let hypr = json!([
+            {"name": "DP-1", "x": 0, "y": 0, "width": 3840, "height": 2160, "scale": 2.0, "transform": 0, "focused": false},
+            {"name": "HDMI-A-1", "x": 1920, "y": 0, "width": 1920, "height": 1080, "scale": 1.0, "transform": 1, "focused": true},
+            {"name": "eDP-1", "x": 0, "y": 0, "width": 1, "height": 1, "scale": 1.0, "disabled": true}
+        ]);
+        assert_eq!(
+            hyprland_outputs(&hypr).unwrap(),
+            [
+                (geo("0,0 1920x1080"), false),
+                (geo("1920,0 1080x1920"), true)
+            ]
+        );
+
+        let wlr = json!([{
+            "name": "DP-1", "enabled": true, "scale": 1.5, "transform": "normal",
+            "position": {"x": -2560, "y": 0},
+            "modes": [
+                {"width": 1920, "height": 1080, "current": false},
+                {"width": 3840, "height": 2160, "current": true}
+            ]
+        }]);
+        assert_eq!(
+            wlr_randr_outputs(&wlr).unwrap(),
+            [(geo("-2560,0 2560x1440"), false)]
+        );
+
+        let sway = json!([
+            {"name": "DP-1", "active": true, "focused": true, "rect": {"x": 0, "y": 0, "width": 2560, "height": 1440}},
+            {"name": "DP-2", "active": false, "rect": {"x": 0, "y": 0, "width": 0, "height": 0}}
+        ]);
+        assert_eq!(sway_outputs(&sway).unwrap(), [(geo("0,0 2560x1440"), true)]);
+    }
+
+    #[test]
+    fn hyprland_windows_on_shown_workspaces() {
+        let active = json!({"address": "0x1", "at": [10, 40], "size": [800, 600]});
+        assert_eq!(hyprland_window(&active).unwrap(), geo("10,40 800x600"));
+        assert!(hyprland_window(&json!({})).is_err());
+
+        let monitors = json!([{"activeWorkspace": {"id": 1}}, {"activeWorkspace": {"id": 4}}]);
+        let clients = json!([
+            {"at": [0, 0], "size": [100, 100], "mapped": true, "hidden": false, "workspace": {"id": 1}},
+            {"at": [5, 5], "size": [50, 50], "mapped": true, "hidden": false, "workspace": {"id": 2}},
+            {"at": [9, 9], "size": [10, 10], "mapped": true, "hidden": true, "workspace": {"id": 4}},
+            {"at": [2000, 0], "size": [300, 200], "mapped": true, "hidden": false, "workspace": {"id": 4}}
+        ]);
+        assert_eq!(
+            hyprland_clients(&clients, &monitors).unwrap(),
+            [geo("0,0 100x100"), geo("2000,0 300x200")]
+        );
+    }
+
+    #[test]
+    fn sway_tree_windows() {
+        let tree = json!({
+            "type": "root", "pid": null, "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+            "nodes": [{
+                "type": "workspace", "pid": null, "visible": true,
+                "nodes": [
+                    {"type": "con", "pid": 10, "visible": true, "focused": true,
+                     "rect": {"x": 0, "y": 30, "width": 960, "height": 1050}, "nodes": []},
+                    {"type": "con", "pid": 11, "visible": false, "focused": false,
+                     "rect": {"x": 960, "y": 30, "width": 960, "height": 1050}, "nodes": []}
+                ],
+                "floating_nodes": [
+                    {"type": "floating_con", "pid": 12, "visible": true, "focused": false,
+                     "rect": {"x": 100, "y": 100, "width": 300, "height": 200}, "nodes": []}
+                ]
+            }]
+        });
+        let mut windows = sway_windows(&tree);
+        windows.sort_by_key(|(g, _)| g.x);
+        assert_eq!(
+            windows,
+            [
+                (geo("0,30 960x1050"), true),
+                (geo("100,100 300x200"), false)
+            ]
+        );
     }
-    Ok(())
 }
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result, bail};
+
+use crate::region::Geometry;
+use crate::timeouts::Stage;
+use crate::tools;
+
+async fn run(program: &str, args: &[&str]) -> Result<String> {
+    let mut command = tools::command(program);
+    command.args(args);
+    let out = tools::run(command, program, None, Stage::Query).await?;
+    if !out.status.success() {
+        bail!("`{program}` failed");
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// (area, primary) for each monitor, from `xrandr --listmonitors`.
+pub async fn outputs() -> Result<Vec<(Geometry, bool)>> {
+    parse_listmonitors(&run("xrandr", &["--listmonitors"]).await?)
+}
+
+/// The active window's area, from `xdotool`.
+pub async fn active_window() -> Result<Geometry> {
+    parse_window_shell(
+        &run(
+            "xdotool",
+            &["getactivewindow", "getwindowgeometry", "--shell"],
+        )
+        .await?,
+    )
+}
+
+/// `grim -g`-style geometry in X11's `WxH+X+Y` notation, for maim and import.
+pub fn x_geometry(g: &Geometry) -> String {
+    format!("{}x{}+{}+{}", g.width, g.height, g.x, g.y)
+}
+
+// " 0: +*DP-1 2560/597x1440/336+0+0  DP-1"
+fn parse_listmonitors(out: &str) -> Result<Vec<(Geometry, bool)>> {
+    let parse = |line: &str| -> Option<(Geometry, bool)> {
+        let mut cols = line.split_whitespace().skip(1);
+        let name = cols.next()?;
+        let (size, pos) = cols.next()?.split_once('+')?;
+        let (w, h) = size.split_once('x')?;
+        let (x, y) = pos.split_once('+')?;
+        let g = Geometry {
+            x: x.parse().ok()?,
+            y: y.parse().ok()?,
+            width: w.split('/').next()?.parse().ok()?,
+            height: h.split('/').next()?.parse().ok()?,
+        };
+        Some((g, name.trim_start_matches('+').starts_with('*')))
+    };
+    let monitors: Vec<_> = out.lines().skip(1).filter_map(parse).collect();
+    if monitors.is_empty() {
+        bail!("Unexpected `xrandr --listmonitors` output");
+    }
+    Ok(monitors)
+}
+
+// "WINDOW=…\nX=10\nY=20\nWIDTH=800\nHEIGHT=600\nSCREEN=0"
+fn parse_window_shell(out: &str) -> Result<Geometry> {
+    let field = |key: &str| {
+        out.lines()
+            .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+            .with_context(|| format!("No {key} from `xdotool`"))
+    };
+    Ok(Geometry {
+        x: field("X")?.trim().parse()?,
+        y: field("Y")?.trim().parse()?,
+        width: field("WIDTH")?.trim().parse()?,
+        height: field("HEIGHT")?.trim().parse()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_xrandr_and_xdotool() {
+        let monitors = "Monitors: 2\n 0: +*DP-1 2560/597x1440/336+0+0  DP-1\n 1: +HDMI-1 1920/531x1080/299+2560+0  HDMI-1\n";
+        let parsed = parse_listmonitors(monitors).unwrap();
+        assert_eq!(parsed[0], ("0,0 2560x1440".parse().unwrap(), true));
+        assert_eq!(parsed[1], ("2560,0 1920x1080".parse().unwrap(), false));
+        assert!(parse_listmonitors("Monitors: 0\n").is_err());
+
+        let window = "WINDOW=65011723\nX=10\nY=20\nWIDTH=800\nHEIGHT=600\nSCREEN=0\n";
+        let g = parse_window_shell(window).unwrap();
+        assert_eq!(g.to_string(), "10,20 800x600");
+        assert_eq!(x_geometry(&g), "800x600+10+20");
+    }
+}